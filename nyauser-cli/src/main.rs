@@ -1,10 +1,17 @@
-use std::iter::once;
+use std::{
+    iter::once,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{anyhow, bail, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use cli_table::{print_stdout, Table, WithTitle};
 use nyauser_types::{
-    Episode, Profile, PullEntryFilter, PullEntryNamed, PullState, RegexWrapper, Series,
+    Backup, BlockEntry, BlockRequest, BlockRule, Decision, DecisionRecord, Episode, FsckFinding,
+    FsckQuery, GrabRequest, LibraryFile, LibraryImportReport, LibraryImportRequest,
+    LibraryRescanReport, MissingPolicy, ParserKind, Profile, ProfileRef, ProfileTestRequest,
+    ProfileTestResult, PullEntryFilter, PullEntryNamed, PullState, RegexWrapper, ReleaseFilter,
+    RescannedEntry, RestoreMode, RestoreQuery, RestoreReport, SearchCandidate, Series,
     SeriesStatus,
 };
 use regex::Regex;
@@ -45,12 +52,50 @@ enum Mode {
         #[clap(subcommand)]
         mode: SeriesMode,
     },
+    /// Manages releases and episodes that should never be grabbed
+    Block {
+        #[clap(subcommand)]
+        mode: BlockMode,
+    },
     /// Procs an immediate scan of download clients for completed downloads
     Scan,
     /// Procs an immediate search for new torrents
     Search,
     /// Wipes all knowledge of deleted pulls, potentially redownloading them
     WipeDeleted,
+    /// Checks the database for inconsistent indexes, orphaned and corrupt records
+    Fsck {
+        /// Repairs what it finds
+        #[clap(long)]
+        repair: bool,
+    },
+    /// Exports profiles, series, pulls and the blocklist
+    Backup {
+        /// File to write, JSON or YAML by extension. Defaults to JSON on stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Restores an export made by `backup`
+    Restore {
+        /// File to read, JSON or YAML by extension
+        file: PathBuf,
+        /// Removes all existing records first instead of merging
+        #[clap(long)]
+        replace: bool,
+    },
+    /// Applies the daemon's configured file ownership and modes across the existing library
+    FixPermissions,
+    /// Verifies library files, marking moved episodes relocated and deleted ones missing
+    Rescan,
+    /// Imports episodes already present in series' relocate directories as finished pulls
+    Import {
+        /// Only import this series
+        #[clap(short, long)]
+        series: Option<String>,
+        /// Only report what would be imported
+        #[clap(short, long)]
+        dry_run: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -64,6 +109,19 @@ enum ProfileMode {
         /// Name of profile to delete
         name: String,
     },
+    /// Tests a profile's parse regex against release titles
+    Test {
+        /// Name of profile to test
+        name: String,
+        /// Release titles to parse
+        titles: Vec<String>,
+        /// If set, titles found by searching the configured source for this query are tested too
+        #[clap(short, long)]
+        query: Option<String>,
+        /// If set, titles are parsed as for this series, with its season and episode rules
+        #[clap(short, long)]
+        series: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -81,6 +139,42 @@ enum SeriesMode {
         /// Name of series to delete
         name: String,
     },
+    /// Runs a search for a series without grabbing anything, showing what would be grabbed and why
+    Search {
+        /// Name of series to search for
+        name: String,
+    },
+    /// Shows what past search rounds decided about each release found for a series
+    Explain {
+        /// Name of series to explain
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BlockMode {
+    List,
+    Add {
+        #[clap(value_enum)]
+        kind: BlockKind,
+        /// Episode id (see `pull list`), info-hash, release group or title regex to block
+        value: String,
+        /// Why this is blocked
+        #[clap(short, long)]
+        reason: Option<String>,
+    },
+    Delete {
+        /// ID of block to delete
+        id: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum BlockKind {
+    Episode,
+    Hash,
+    Group,
+    TitleRegex,
 }
 
 #[derive(Subcommand, Debug)]
@@ -96,6 +190,7 @@ enum PullMode {
         season_is: Option<u32>,
         #[clap(short, long)]
         episode_is: Option<String>,
+        /// One of queued, downloading, importing, imported, finished, failed, ignored, relocated or missing
         #[clap(short = 'm', long)]
         state: Option<String>,
     },
@@ -103,6 +198,15 @@ enum PullMode {
         /// ID of pull to delete
         id: String,
     },
+    /// Lists pulls waiting to retry a failed push to the sink
+    Retries,
+    /// Manually grabs a release found by `series search`
+    Grab {
+        /// Name of series the release belongs to
+        series: String,
+        /// Release title or view link of the search result to grab
+        release: String,
+    },
 }
 
 lazy_static::lazy_static! {
@@ -198,7 +302,10 @@ impl From<Profile> for ProfileTable {
         Self {
             name: value.name,
             search_prefix: value.search_prefix.unwrap_or_default(),
-            parse_regex: value.parse_regex.to_string(),
+            parse_regex: match value.parser {
+                ParserKind::Auto => "auto".to_string(),
+                ParserKind::Regex => value.parse_regex.map(|x| x.to_string()).unwrap_or_default(),
+            },
             relocate: value.relocate.unwrap_or_default(),
         }
     }
@@ -208,6 +315,8 @@ impl From<Profile> for ProfileTable {
 struct SeriesTable {
     #[table(title = "Name")]
     name: String,
+    #[table(title = "Aliases")]
+    aliases: String,
     #[table(title = "Profile")]
     profile: String,
     #[table(title = "Max Days Old")]
@@ -216,12 +325,17 @@ struct SeriesTable {
     relocate: String,
     #[table(title = "Relocate Season")]
     relocate_season: bool,
+    #[table(title = "Max Active Downloads")]
+    max_active_downloads: String,
+    #[table(title = "Missing Policy")]
+    missing_policy: String,
 }
 
 impl From<Series> for SeriesTable {
     fn from(value: Series) -> Self {
         Self {
             name: value.name,
+            aliases: value.aliases.join(", "),
             profile: value.profile,
             max_days_old: value
                 .max_days_old
@@ -229,6 +343,11 @@ impl From<Series> for SeriesTable {
                 .unwrap_or_default(),
             relocate: value.relocate.unwrap_or_default(),
             relocate_season: value.relocate_season,
+            max_active_downloads: value
+                .max_active_downloads
+                .map(|x| x.to_string())
+                .unwrap_or_default(),
+            missing_policy: format!("{:?}", value.missing_policy).to_lowercase(),
         }
     }
 }
@@ -251,11 +370,57 @@ struct PullTable {
     torrent_id: String,
     #[table(title = "Torrent Hash")]
     torrent_hash: String,
+    #[table(title = "Progress")]
+    progress: String,
+    #[table(title = "Rate")]
+    rate: String,
+    #[table(title = "ETA")]
+    eta: String,
+    #[table(title = "Size")]
+    size: String,
+    #[table(title = "Peers")]
+    peers: String,
+    #[table(title = "Reason")]
+    reason: String,
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn format_seconds(seconds: u64) -> String {
+    let (hours, minutes) = (seconds / 3600, seconds / 60 % 60);
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m")
+    } else {
+        format!("{minutes}m{:02}s", seconds % 60)
+    }
 }
 
 impl From<PullEntryNamed> for PullTable {
     fn from(value: PullEntryNamed) -> Self {
+        let progress = value.pull_entry.progress.as_ref();
         Self {
+            progress: progress
+                .map(|x| format!("{:.1}%", x.percent_done * 100.0))
+                .unwrap_or_default(),
+            rate: progress
+                .map(|x| format!("{}/s", format_bytes(x.rate_download)))
+                .unwrap_or_default(),
+            eta: progress
+                .and_then(|x| x.eta_seconds)
+                .map(format_seconds)
+                .unwrap_or_default(),
+            size: progress.map(|x| format_bytes(x.size)).unwrap_or_default(),
+            peers: progress.map(|x| x.peers.to_string()).unwrap_or_default(),
+            reason: value.pull_entry.reason().unwrap_or_default().to_string(),
             id: value.id,
             state: value.pull_entry.state,
             title: value.pull_entry.result.parsed.title,
@@ -272,22 +437,266 @@ impl From<PullEntryNamed> for PullTable {
     }
 }
 
+#[derive(Table)]
+struct RetryTable {
+    #[table(title = "Id")]
+    id: String,
+    #[table(title = "State")]
+    state: PullState,
+    #[table(title = "Attempts")]
+    attempts: u32,
+    #[table(title = "Next Attempt")]
+    next_attempt: String,
+    #[table(title = "Reason")]
+    reason: String,
+}
+
+impl From<PullEntryNamed> for RetryTable {
+    fn from(value: PullEntryNamed) -> Self {
+        let retry = value.pull_entry.retry.as_ref();
+        Self {
+            attempts: retry.map(|x| x.attempts).unwrap_or_default(),
+            next_attempt: match retry.map(|x| x.next_attempt) {
+                Some(Some(at)) => at.to_rfc3339(),
+                Some(None) => "gave up".to_string(),
+                None => String::new(),
+            },
+            reason: value.pull_entry.reason().unwrap_or_default().to_string(),
+            id: value.id,
+            state: value.pull_entry.state,
+        }
+    }
+}
+
+#[derive(Table)]
+struct LibraryFileTable {
+    #[table(title = "Status")]
+    status: &'static str,
+    #[table(title = "Series")]
+    series: String,
+    #[table(title = "Key")]
+    key: String,
+    #[table(title = "Path")]
+    path: String,
+    #[table(title = "Error")]
+    error: String,
+}
+
+impl LibraryFileTable {
+    fn new(status: &'static str, file: LibraryFile) -> Self {
+        Self {
+            status,
+            series: file.series,
+            key: file.key.unwrap_or_default(),
+            path: file.path,
+            error: file.error.unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Table)]
+struct RescanTable {
+    #[table(title = "Status")]
+    status: &'static str,
+    #[table(title = "Key")]
+    key: String,
+    #[table(title = "Files")]
+    files: String,
+}
+
+impl RescanTable {
+    fn new(status: &'static str, entry: RescannedEntry) -> Self {
+        Self {
+            status,
+            key: entry.key,
+            files: entry.files.join("\n"),
+        }
+    }
+}
+
+#[derive(Table)]
+struct FsckTable {
+    #[table(title = "Issue")]
+    issue: String,
+    #[table(title = "Repaired")]
+    repaired: bool,
+}
+
+impl From<FsckFinding> for FsckTable {
+    fn from(value: FsckFinding) -> Self {
+        Self {
+            issue: value.issue.to_string(),
+            repaired: value.repaired,
+        }
+    }
+}
+
+#[derive(Table)]
+struct BlockTable {
+    #[table(title = "Id")]
+    id: String,
+    #[table(title = "Kind")]
+    kind: String,
+    #[table(title = "Value")]
+    value: String,
+    #[table(title = "Reason")]
+    reason: String,
+    #[table(title = "Created")]
+    created: String,
+}
+
+impl From<BlockEntry> for BlockTable {
+    fn from(value: BlockEntry) -> Self {
+        Self {
+            id: value.id(),
+            kind: value.rule.kind().to_string(),
+            value: value.rule.value().to_string(),
+            reason: value.reason.unwrap_or_default(),
+            created: value.created.format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+}
+
+#[derive(Table)]
+struct CandidateTable {
+    #[table(title = "Release")]
+    release: String,
+    #[table(title = "Date")]
+    date: String,
+    #[table(title = "Seeders")]
+    seeders: u64,
+    #[table(title = "Id")]
+    id: String,
+    #[table(title = "Decision")]
+    decision: Decision,
+}
+
+impl From<SearchCandidate> for CandidateTable {
+    fn from(value: SearchCandidate) -> Self {
+        Self {
+            release: value.result.title,
+            date: value.result.date.format("%Y-%m-%d %H:%M").to_string(),
+            seeders: value.result.seeders,
+            id: value.parsed.map(|x| x.key()).unwrap_or_default(),
+            decision: value.decision,
+        }
+    }
+}
+
+#[derive(Table)]
+struct DecisionTable {
+    #[table(title = "Time")]
+    at: String,
+    #[table(title = "Release")]
+    release: String,
+    #[table(title = "Id")]
+    id: String,
+    #[table(title = "Decision")]
+    decision: Decision,
+}
+
+impl From<DecisionRecord> for DecisionTable {
+    fn from(value: DecisionRecord) -> Self {
+        Self {
+            at: value.at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            release: value.title,
+            id: value.key.unwrap_or_default(),
+            decision: value.decision,
+        }
+    }
+}
+
+#[derive(Table)]
+struct ProfileTestTable {
+    #[table(title = "Release")]
+    release: String,
+    #[table(title = "Title")]
+    title: String,
+    #[table(title = "Season")]
+    season: String,
+    #[table(title = "Episode")]
+    episode: String,
+    #[table(title = "Group")]
+    group: String,
+    #[table(title = "Resolution")]
+    resolution: String,
+    #[table(title = "Source")]
+    source: String,
+    #[table(title = "Error")]
+    error: String,
+}
+
+impl From<ProfileTestResult> for ProfileTestTable {
+    fn from(value: ProfileTestResult) -> Self {
+        let parsed = value.parsed.unwrap_or_default();
+        Self {
+            release: value.title,
+            title: parsed.title,
+            season: parsed.season.to_string(),
+            episode: parsed.episode.to_string(),
+            group: parsed.release_group.unwrap_or_default(),
+            resolution: parsed
+                .resolution
+                .map(|x| format!("{x}p"))
+                .unwrap_or_default(),
+            source: parsed.source.map(|x| x.to_string()).unwrap_or_default(),
+            error: value.error.unwrap_or_default(),
+        }
+    }
+}
+
 lazy_static::lazy_static! {
     static ref DEFAULT_PROFILE: Profile = Profile {
         name: String::new(),
         search_prefix: Some("subsplease 1080p".to_string()),
-        parse_regex: RegexWrapper(Regex::new(r"\[SubsPlease\] (?P<title>.*?) (?:S(?P<season>[0-9]{1,2}) )?- (?P<episode>(?:SP)?[0-9]{1,3}(?:\.\d)?)(?:v[0-9])? \(1080p\) \[(?P<checksum>[0-9a-zA-Z]{8})\]\.mkv").unwrap()),
+        parser: ParserKind::Regex,
+        parse_regex: Some(RegexWrapper(Regex::new(r"\[SubsPlease\] (?P<title>.*?) (?:S(?P<season>[0-9]{1,2}) )?- (?P<episode>(?:SP)?[0-9]{1,3}(?:\.\d)?)(?:v[0-9])? \(1080p\) \[(?P<checksum>[0-9a-zA-Z]{8})\]\.mkv").unwrap())),
+        filter: ReleaseFilter::default(),
+        rank: vec![],
         relocate: None,
     };
     static ref DEFAULT_SERIES: Series = Series {
         name: String::new(),
+        aliases: vec![],
         profile: "subsplease".to_string(),
         max_days_old: None,
         relocate: None,
         relocate_season: true,
+        max_active_downloads: None,
+        missing_policy: MissingPolicy::Keep,
+        season_rules: vec![],
+        episode_rules: vec![],
+        specials_season_zero: false,
     };
 }
 
+fn is_yaml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|x| x.to_str()),
+        Some("yml" | "yaml")
+    )
+}
+
+async fn search_series(name: &str) -> Result<Vec<SearchCandidate>> {
+    let response = api(
+        Method::GET,
+        "/api/v1/series"
+            .split('/')
+            .chain(once(name))
+            .chain(once("search")),
+    )?
+    .send()
+    .await?;
+    if !response.status().is_success() {
+        bail!(
+            "Got HTTP Status: {}\n{}",
+            response.status(),
+            response.text().await?
+        );
+    }
+    Ok(response.json().await?)
+}
+
 async fn execute_mode(mode: &Mode) -> Result<()> {
     match mode {
         Mode::Profile { mode } => match mode {
@@ -361,6 +770,32 @@ async fn execute_mode(mode: &Mode) -> Result<()> {
                     );
                 }
             }
+            ProfileMode::Test {
+                name,
+                titles,
+                query,
+                series,
+            } => {
+                let response = api(Method::POST, "/api/v1/profile/test".split('/'))?
+                    .json(&ProfileTestRequest {
+                        profile: ProfileRef::Named(name.clone()),
+                        titles: titles.clone(),
+                        query: query.clone(),
+                        series: series.clone(),
+                    })
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    bail!(
+                        "Got HTTP Status: {}\n{}",
+                        response.status(),
+                        response.text().await?
+                    );
+                }
+                let list: Vec<ProfileTestResult> = response.json().await?;
+                let list: Vec<ProfileTestTable> = list.into_iter().map(Into::into).collect();
+                print_stdout(list.with_title()).unwrap();
+            }
         },
         Mode::Pull { mode } => match mode {
             PullMode::List {
@@ -376,7 +811,7 @@ async fn execute_mode(mode: &Mode) -> Result<()> {
                         profile: profile.clone(),
                         title_contains: title_contains.clone(),
                         title_is: title_is.clone(),
-                        season_is: season_is.clone(),
+                        season_is: *season_is,
                         episode_is: episode_is.as_ref().map(|x| x.parse().unwrap()),
                         state: state
                             .as_ref()
@@ -409,6 +844,46 @@ async fn execute_mode(mode: &Mode) -> Result<()> {
                     );
                 }
             }
+            PullMode::Retries => {
+                let response = api(Method::GET, "/api/v1/pull/retry".split('/'))?
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    bail!(
+                        "Got HTTP Status: {}\n{}",
+                        response.status(),
+                        response.text().await?
+                    );
+                }
+                let list: Vec<PullEntryNamed> = response.json().await?;
+                let list: Vec<RetryTable> = list.into_iter().map(Into::into).collect();
+                print_stdout(list.with_title()).unwrap();
+            }
+            PullMode::Grab { series, release } => {
+                let candidates = search_series(series).await?;
+                let Some(candidate) = candidates.into_iter().find(|candidate| {
+                    &candidate.result.title == release || &candidate.result.view_link == release
+                }) else {
+                    bail!("release not found in search results for '{series}'");
+                };
+                let response = api(Method::POST, "/api/v1/pull/grab".split('/'))?
+                    .json(&GrabRequest {
+                        series: series.clone(),
+                        result: candidate.result,
+                    })
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    bail!(
+                        "Got HTTP Status: {}\n{}",
+                        response.status(),
+                        response.text().await?
+                    );
+                }
+                let pull: PullEntryNamed = response.json().await?;
+                let list: Vec<PullTable> = vec![pull.into()];
+                print_stdout(list.with_title()).unwrap();
+            }
         },
         Mode::Series { mode } => match mode {
             SeriesMode::List => {
@@ -501,6 +976,93 @@ async fn execute_mode(mode: &Mode) -> Result<()> {
                     );
                 }
             }
+            SeriesMode::Explain { name } => {
+                let response = api(
+                    Method::GET,
+                    "/api/v1/series"
+                        .split('/')
+                        .chain(once(&**name))
+                        .chain(once("history")),
+                )?
+                .send()
+                .await?;
+                if !response.status().is_success() {
+                    bail!(
+                        "Got HTTP Status: {}\n{}",
+                        response.status(),
+                        response.text().await?
+                    );
+                }
+                let list: Vec<DecisionRecord> = response.json().await?;
+                let list: Vec<DecisionTable> = list.into_iter().map(Into::into).collect();
+                print_stdout(list.with_title()).unwrap();
+            }
+            SeriesMode::Search { name } => {
+                let list: Vec<CandidateTable> = search_series(name)
+                    .await?
+                    .into_iter()
+                    .map(Into::into)
+                    .collect();
+                print_stdout(list.with_title()).unwrap();
+            }
+        },
+        Mode::Block { mode } => match mode {
+            BlockMode::List => {
+                let response = api(Method::GET, "/api/v1/block".split('/'))?.send().await?;
+                if !response.status().is_success() {
+                    bail!(
+                        "Got HTTP Status: {}\n{}",
+                        response.status(),
+                        response.text().await?
+                    );
+                }
+                let list: Vec<BlockEntry> = response.json().await?;
+                let list: Vec<BlockTable> = list.into_iter().map(Into::into).collect();
+                print_stdout(list.with_title()).unwrap();
+            }
+            BlockMode::Add {
+                kind,
+                value,
+                reason,
+            } => {
+                let rule = match kind {
+                    BlockKind::Episode => BlockRule::Episode(value.clone()),
+                    BlockKind::Hash => BlockRule::Hash(value.clone()),
+                    BlockKind::Group => BlockRule::Group(value.clone()),
+                    BlockKind::TitleRegex => {
+                        BlockRule::TitleRegex(RegexWrapper(Regex::new(value)?))
+                    }
+                };
+                let response = api(Method::POST, "/api/v1/block".split('/'))?
+                    .json(&BlockRequest {
+                        rule,
+                        reason: reason.clone(),
+                    })
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    bail!(
+                        "Got HTTP Status: {}\n{}",
+                        response.status(),
+                        response.text().await?
+                    );
+                }
+            }
+            BlockMode::Delete { id } => {
+                let response = api(
+                    Method::DELETE,
+                    "/api/v1/block".split('/').chain(once(&**id)),
+                )?
+                .send()
+                .await?;
+                if !response.status().is_success() {
+                    bail!(
+                        "Got HTTP Status: {}\n{}",
+                        response.status(),
+                        response.text().await?
+                    );
+                }
+            }
         },
         Mode::Scan => {
             let response = api(Method::GET, "/api/v1/proc/scan".split('/'))?
@@ -538,6 +1100,165 @@ async fn execute_mode(mode: &Mode) -> Result<()> {
                 );
             }
         }
+        Mode::Fsck { repair } => {
+            let response = api(Method::POST, "/api/v1/proc/fsck".split('/'))?
+                .query(&FsckQuery { repair: *repair })
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                bail!(
+                    "Got HTTP Status: {}\n{}",
+                    response.status(),
+                    response.text().await?
+                );
+            }
+            let findings: Vec<FsckFinding> = response.json().await?;
+            let list: Vec<FsckTable> = findings.into_iter().map(Into::into).collect();
+            print_stdout(list.with_title()).unwrap();
+        }
+        Mode::Backup { output } => {
+            let response = api(Method::GET, "/api/v1/backup".split('/'))?
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                bail!(
+                    "Got HTTP Status: {}\n{}",
+                    response.status(),
+                    response.text().await?
+                );
+            }
+            let backup: Backup = response.json().await?;
+            match output {
+                Some(path) if is_yaml(path) => {
+                    std::fs::write(path, serde_yaml::to_string(&backup)?)?
+                }
+                Some(path) => std::fs::write(path, serde_json::to_string_pretty(&backup)?)?,
+                None => println!("{}", serde_json::to_string_pretty(&backup)?),
+            }
+        }
+        Mode::Restore { file, replace } => {
+            let raw = std::fs::read_to_string(file)?;
+            let backup: Backup = if is_yaml(file) {
+                serde_yaml::from_str(&raw)?
+            } else {
+                serde_json::from_str(&raw)?
+            };
+            let response = api(Method::POST, "/api/v1/backup".split('/'))?
+                .query(&RestoreQuery {
+                    mode: if *replace {
+                        RestoreMode::Replace
+                    } else {
+                        RestoreMode::Merge
+                    },
+                })
+                .json(&backup)
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                bail!(
+                    "Got HTTP Status: {}\n{}",
+                    response.status(),
+                    response.text().await?
+                );
+            }
+            let report: RestoreReport = response.json().await?;
+            println!(
+                "restored {} profiles, {} series, {} pulls and {} blocklist entries, removed {} records",
+                report.profiles, report.series, report.pull_entries, report.blocklist, report.removed
+            );
+        }
+        Mode::FixPermissions => {
+            let response = api(Method::POST, "/api/v1/proc/fix_permissions".split('/'))?
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                bail!(
+                    "Got HTTP Status: {}\n{}",
+                    response.status(),
+                    response.text().await?
+                );
+            }
+            let count: usize = response.json().await?;
+            println!("fixed permissions of {count} files and directories");
+        }
+        Mode::Rescan => {
+            let response = api(Method::POST, "/api/v1/proc/rescan".split('/'))?
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                bail!(
+                    "Got HTTP Status: {}\n{}",
+                    response.status(),
+                    response.text().await?
+                );
+            }
+            let report: LibraryRescanReport = response.json().await?;
+            let list: Vec<RescanTable> = report
+                .relocated
+                .into_iter()
+                .map(|x| RescanTable::new("relocated", x))
+                .chain(
+                    report
+                        .missing
+                        .into_iter()
+                        .map(|x| RescanTable::new("missing", x)),
+                )
+                .chain(
+                    report
+                        .restored
+                        .into_iter()
+                        .map(|x| RescanTable::new("restored", x)),
+                )
+                .collect();
+            print_stdout(list.with_title()).unwrap();
+        }
+        Mode::Import { series, dry_run } => {
+            let response = api(Method::POST, "/api/v1/proc/import".split('/'))?
+                .json(&LibraryImportRequest {
+                    dry_run: *dry_run,
+                    series: series.clone(),
+                })
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                bail!(
+                    "Got HTTP Status: {}\n{}",
+                    response.status(),
+                    response.text().await?
+                );
+            }
+            let report: LibraryImportReport = response.json().await?;
+            let imported = if *dry_run { "would import" } else { "imported" };
+            let list: Vec<LibraryFileTable> = report
+                .imported
+                .into_iter()
+                .map(|x| LibraryFileTable::new(imported, x))
+                .chain(
+                    report
+                        .existing
+                        .into_iter()
+                        .map(|x| LibraryFileTable::new("existing", x)),
+                )
+                .chain(
+                    report
+                        .unparsed
+                        .into_iter()
+                        .map(|x| LibraryFileTable::new("unparsed", x)),
+                )
+                .chain(report.skipped.into_iter().map(|x| {
+                    LibraryFileTable::new(
+                        "skipped",
+                        LibraryFile {
+                            series: x.series,
+                            path: String::new(),
+                            key: None,
+                            error: Some(x.error),
+                        },
+                    )
+                }))
+                .collect();
+            print_stdout(list.with_title()).unwrap();
+        }
     }
     Ok(())
 }
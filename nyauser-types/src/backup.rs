@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{BlockEntry, Profile, PullEntry, Series};
+
+/// bumped whenever `Backup` changes incompatibly
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Everything needed to rebuild a database on another host.
+/// Search decision history is not included.
+#[derive(Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u32,
+    pub created: DateTime<Utc>,
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    #[serde(default)]
+    pub series: Vec<Series>,
+    #[serde(default)]
+    pub pull_entries: Vec<PullEntry>,
+    #[serde(default)]
+    pub blocklist: Vec<BlockEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreMode {
+    /// records in the backup overwrite ones with the same key, others are kept
+    #[default]
+    Merge,
+    /// all existing records are removed first
+    Replace,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RestoreQuery {
+    #[serde(default)]
+    pub mode: RestoreMode,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RestoreReport {
+    pub profiles: usize,
+    pub series: usize,
+    pub pull_entries: usize,
+    pub blocklist: usize,
+    /// records removed before restoring, for `RestoreMode::Replace`
+    pub removed: usize,
+}
@@ -0,0 +1,98 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{ParsedSearchResult, RegexWrapper, SearchResult};
+
+/// Something that should never be grabbed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum BlockRule {
+    /// a pull entry key, i.e. an episode of a series
+    Episode(String),
+    /// a torrent info-hash
+    Hash(String),
+    /// a release group or uploader, i.e. `SubsPlease` for `[SubsPlease] ...`. The parsed
+    /// release group is used if there is one
+    Group(String),
+    /// matched against release titles
+    TitleRegex(RegexWrapper),
+}
+
+impl BlockRule {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BlockRule::Episode(_) => "episode",
+            BlockRule::Hash(_) => "hash",
+            BlockRule::Group(_) => "group",
+            BlockRule::TitleRegex(_) => "title_regex",
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        match self {
+            BlockRule::Episode(x) | BlockRule::Hash(x) | BlockRule::Group(x) => x,
+            BlockRule::TitleRegex(x) => x.as_str(),
+        }
+    }
+
+    /// unique per rule, used as the storage and API key
+    pub fn id(&self) -> String {
+        format!("{}-{}", self.kind(), self.value())
+    }
+
+    /// `parsed` is the result parsed by its series' profile, if it parsed
+    pub fn matches(&self, result: &SearchResult, parsed: Option<&ParsedSearchResult>) -> bool {
+        match self {
+            BlockRule::Episode(episode) => parsed.map_or(false, |x| x.key() == *episode),
+            BlockRule::Hash(hash) => result
+                .info_hash
+                .as_ref()
+                .map(|x| x.eq_ignore_ascii_case(hash))
+                .unwrap_or(false),
+            BlockRule::Group(group) => parsed
+                .and_then(|x| x.parsed.release_group.as_deref())
+                .or_else(|| release_group(&result.title))
+                .map(|x| x.eq_ignore_ascii_case(group))
+                .unwrap_or(false),
+            BlockRule::TitleRegex(regex) => regex.is_match(&result.title),
+        }
+    }
+}
+
+/// the leading `[Group]` of a release title
+pub fn release_group(title: &str) -> Option<&str> {
+    let (group, _) = title.trim_start().strip_prefix('[')?.split_once(']')?;
+    Some(group.trim())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockEntry {
+    #[serde(flatten)]
+    pub rule: BlockRule,
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub created: DateTime<Utc>,
+}
+
+impl BlockEntry {
+    pub fn new(rule: BlockRule, reason: Option<String>) -> Self {
+        Self {
+            rule,
+            reason,
+            created: Utc::now(),
+        }
+    }
+
+    pub fn id(&self) -> String {
+        self.rule.id()
+    }
+}
+
+/// Request to add a block rule
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockRequest {
+    #[serde(flatten)]
+    pub rule: BlockRule,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{BlockRule, ParsedSearchResult, SearchResult};
+
+/// Why a search result was not grabbed
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RejectReason {
+    TooOld {
+        days_old: i64,
+        max_days_old: u64,
+    },
+    TooFewSeeders {
+        seeders: u64,
+        min_seeders: u64,
+    },
+    ParseFailure {
+        error: String,
+    },
+    /// the parsed title is neither the series name nor one of its aliases
+    TitleMismatch {
+        title: String,
+    },
+    /// rejected by the profile's release filter
+    Filtered {
+        reason: String,
+    },
+    /// a pull entry already exists for this episode
+    AlreadyPresent {
+        key: String,
+    },
+    /// an earlier result in the same search already claimed this episode
+    DuplicateCandidate {
+        key: String,
+    },
+    /// matched the blocklist
+    Blocked {
+        rule: BlockRule,
+        reason: Option<String>,
+    },
+    /// the sink already had this torrent
+    AlreadyInSink,
+    PushFailed {
+        error: String,
+    },
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::TooOld {
+                days_old,
+                max_days_old,
+            } => write!(f, "too old ({days_old} > {max_days_old} days)"),
+            RejectReason::TooFewSeeders {
+                seeders,
+                min_seeders,
+            } => write!(f, "too few seeders ({seeders} < {min_seeders})"),
+            RejectReason::ParseFailure { error } => write!(f, "parse failure: {error}"),
+            RejectReason::TitleMismatch { title } => write!(f, "title mismatch: {title}"),
+            RejectReason::Filtered { reason } => write!(f, "filtered: {reason}"),
+            RejectReason::AlreadyPresent { key } => write!(f, "already present: {key}"),
+            RejectReason::DuplicateCandidate { key } => write!(f, "duplicate candidate: {key}"),
+            RejectReason::Blocked { rule, reason } => {
+                write!(f, "blocked by {}", rule.id())?;
+                if let Some(reason) = reason {
+                    write!(f, " ({reason})")?;
+                }
+                Ok(())
+            }
+            RejectReason::AlreadyInSink => write!(f, "already present in sink"),
+            RejectReason::PushFailed { error } => write!(f, "push failed: {error}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum Decision {
+    Accept,
+    Reject { reason: RejectReason },
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::Accept => write!(f, "accept"),
+            Decision::Reject { reason } => write!(f, "reject: {reason}"),
+        }
+    }
+}
+
+/// A search result as evaluated by a search round
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchCandidate {
+    pub result: SearchResult,
+    /// set if the title parsed
+    pub parsed: Option<ParsedSearchResult>,
+    #[serde(flatten)]
+    pub decision: Decision,
+}
+
+/// A decision made about a search result during a search round
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    pub series: String,
+    pub at: DateTime<Utc>,
+    pub title: String,
+    pub view_link: String,
+    /// pull entry key, if the title parsed
+    pub key: Option<String>,
+    #[serde(flatten)]
+    pub decision: Decision,
+}
+
+/// Request to manually grab a search result for a series
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GrabRequest {
+    pub series: String,
+    pub result: SearchResult,
+}
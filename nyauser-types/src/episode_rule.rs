@@ -0,0 +1,230 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{Episode, StandardEpisode};
+
+/// Maps a parsed release onto the series' canonical season and episode, so releases from
+/// groups numbering differently land on the same episode. Only numbered episodes are mapped.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EpisodeRule {
+    /// shifts the season and episode numbers of releases in `season`, or of every release
+    Offset {
+        #[serde(default)]
+        season: Option<u32>,
+        #[serde(default)]
+        season_offset: i32,
+        #[serde(default)]
+        episode_offset: i32,
+    },
+    /// episodes `from..=to` of `season` are `target_season` episodes starting at `target_start`,
+    /// i.e. "episodes 13-24 are season 2 episodes 1-12"
+    Range {
+        #[serde(default = "default_season")]
+        season: u32,
+        from: u32,
+        to: u32,
+        target_season: u32,
+        #[serde(default = "default_target_start")]
+        target_start: u32,
+    },
+    /// releases numbered absolutely, which parse as season 1, are split into seasons by
+    /// `episodes`, the episode count of each season in order. Episodes past the last season
+    /// are counted in the last season.
+    Absolute { episodes: Vec<u32> },
+}
+
+fn default_season() -> u32 {
+    1
+}
+
+fn default_target_start() -> u32 {
+    1
+}
+
+impl EpisodeRule {
+    /// Some((season, episode)) if the rule applies
+    fn map(&self, season: u32, episode: u32) -> Result<Option<(u32, u32)>> {
+        match self {
+            EpisodeRule::Offset {
+                season: only,
+                season_offset,
+                episode_offset,
+            } => {
+                if only.map_or(false, |x| x != season) {
+                    return Ok(None);
+                }
+                let season = season
+                    .checked_add_signed(*season_offset)
+                    .ok_or_else(|| anyhow!("season offset out of range for season {season}"))?;
+                let episode = episode
+                    .checked_add_signed(*episode_offset)
+                    .ok_or_else(|| anyhow!("episode offset out of range for episode {episode}"))?;
+                Ok(Some((season, episode)))
+            }
+            EpisodeRule::Range {
+                season: only,
+                from,
+                to,
+                target_season,
+                target_start,
+            } => {
+                if *only != season || !(*from..=*to).contains(&episode) {
+                    return Ok(None);
+                }
+                Ok(Some((*target_season, episode - from + target_start)))
+            }
+            EpisodeRule::Absolute { episodes } => {
+                if season != 1 || episodes.is_empty() {
+                    return Ok(None);
+                }
+                let mut remaining = episode;
+                for (i, count) in episodes.iter().enumerate() {
+                    if remaining <= *count || i == episodes.len() - 1 {
+                        return Ok(Some((i as u32 + 1, remaining)));
+                    }
+                    remaining -= count;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Applies the first matching rule, if any. Specials are left alone.
+pub fn apply_episode_rules(rules: &[EpisodeRule], parsed: &mut StandardEpisode) -> Result<()> {
+    let Episode::Standard(episode) = parsed.episode else {
+        return Ok(());
+    };
+    for rule in rules {
+        if let Some((season, episode)) = rule.map(parsed.season, episode)? {
+            parsed.season = season;
+            parsed.episode = Episode::Standard(episode);
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapped(rules: &[EpisodeRule], season: u32, episode: Episode) -> (u32, Episode) {
+        let mut parsed = StandardEpisode {
+            season,
+            episode,
+            ..Default::default()
+        };
+        apply_episode_rules(rules, &mut parsed).unwrap();
+        (parsed.season, parsed.episode)
+    }
+
+    #[test]
+    fn test_range() {
+        let rules = [EpisodeRule::Range {
+            season: 1,
+            from: 13,
+            to: 24,
+            target_season: 2,
+            target_start: 1,
+        }];
+        assert_eq!(
+            mapped(&rules, 1, Episode::Standard(12)),
+            (1, Episode::Standard(12))
+        );
+        assert_eq!(
+            mapped(&rules, 1, Episode::Standard(13)),
+            (2, Episode::Standard(1))
+        );
+        assert_eq!(
+            mapped(&rules, 1, Episode::Standard(24)),
+            (2, Episode::Standard(12))
+        );
+        assert_eq!(
+            mapped(&rules, 2, Episode::Standard(13)),
+            (2, Episode::Standard(13))
+        );
+    }
+
+    #[test]
+    fn test_offset() {
+        let rules = [EpisodeRule::Offset {
+            season: Some(2),
+            season_offset: 1,
+            episode_offset: -12,
+        }];
+        assert_eq!(
+            mapped(&rules, 2, Episode::Standard(13)),
+            (3, Episode::Standard(1))
+        );
+        assert_eq!(
+            mapped(&rules, 1, Episode::Standard(13)),
+            (1, Episode::Standard(13))
+        );
+        let mut parsed = StandardEpisode {
+            season: 2,
+            episode: Episode::Standard(5),
+            ..Default::default()
+        };
+        assert!(apply_episode_rules(&rules, &mut parsed).is_err());
+    }
+
+    #[test]
+    fn test_absolute() {
+        let rules = [EpisodeRule::Absolute {
+            episodes: vec![12, 13, 12],
+        }];
+        assert_eq!(
+            mapped(&rules, 1, Episode::Standard(12)),
+            (1, Episode::Standard(12))
+        );
+        assert_eq!(
+            mapped(&rules, 1, Episode::Standard(25)),
+            (2, Episode::Standard(13))
+        );
+        assert_eq!(
+            mapped(&rules, 1, Episode::Standard(26)),
+            (3, Episode::Standard(1))
+        );
+        assert_eq!(
+            mapped(&rules, 1, Episode::Standard(40)),
+            (3, Episode::Standard(15))
+        );
+        // already split into seasons by the group
+        assert_eq!(
+            mapped(&rules, 2, Episode::Standard(3)),
+            (2, Episode::Standard(3))
+        );
+        assert_eq!(
+            mapped(&rules, 1, Episode::Special(Some(1))),
+            (1, Episode::Special(Some(1)))
+        );
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        let rules = [
+            EpisodeRule::Range {
+                season: 1,
+                from: 1,
+                to: 12,
+                target_season: 1,
+                target_start: 1,
+            },
+            EpisodeRule::Offset {
+                season: None,
+                season_offset: 0,
+                episode_offset: -12,
+            },
+        ];
+        assert_eq!(
+            mapped(&rules, 1, Episode::Standard(3)),
+            (1, Episode::Standard(3))
+        );
+        assert_eq!(
+            mapped(&rules, 1, Episode::Standard(15)),
+            (1, Episode::Standard(3))
+        );
+    }
+}
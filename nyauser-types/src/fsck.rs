@@ -0,0 +1,60 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FsckIssue {
+    /// a record that doesn't deserialize, repaired by moving it under the `corrupt-` prefix
+    Corrupt { key: String, error: String },
+    /// a `downloading-` index pointing at a missing pull entry, repaired by removing it
+    DanglingIndex { torrent_id: i64, key: String },
+    /// a `downloading-` index pointing at a pull entry with another torrent id, repaired by removing it
+    MismatchedIndex { torrent_id: i64, key: String },
+    /// a pull entry with a torrent id that isn't indexed, repaired by indexing it
+    UnindexedTorrent { torrent_id: i64, key: String },
+    /// a series whose profile doesn't exist, not repairable
+    MissingProfile { series: String, profile: String },
+    /// a pull entry for a deleted series, repaired by deleting it
+    OrphanedPull { key: String, series: String },
+}
+
+impl fmt::Display for FsckIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsckIssue::Corrupt { key, error } => write!(f, "corrupt record '{key}': {error}"),
+            FsckIssue::DanglingIndex { torrent_id, key } => {
+                write!(f, "torrent {torrent_id} indexed to missing pull '{key}'")
+            }
+            FsckIssue::MismatchedIndex { torrent_id, key } => {
+                write!(
+                    f,
+                    "torrent {torrent_id} indexed to pull '{key}' with another torrent"
+                )
+            }
+            FsckIssue::UnindexedTorrent { torrent_id, key } => {
+                write!(f, "pull '{key}' has unindexed torrent {torrent_id}")
+            }
+            FsckIssue::MissingProfile { series, profile } => {
+                write!(f, "series '{series}' uses missing profile '{profile}'")
+            }
+            FsckIssue::OrphanedPull { key, series } => {
+                write!(f, "pull '{key}' belongs to deleted series '{series}'")
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FsckFinding {
+    #[serde(flatten)]
+    pub issue: FsckIssue,
+    pub repaired: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FsckQuery {
+    /// repairs what can be repaired, otherwise only reports
+    #[serde(default)]
+    pub repair: bool,
+}
@@ -1,7 +1,25 @@
+mod backup;
+pub use backup::*;
+mod block;
+pub use block::*;
+mod candidate;
+pub use candidate::*;
+mod episode_rule;
+pub use episode_rule::*;
+mod fsck;
+pub use fsck::*;
+mod library;
+pub use library::*;
 mod profile;
 pub use profile::*;
 mod pull_entry;
 pub use pull_entry::*;
+mod release_filter;
+pub use release_filter::*;
+mod release_parser;
+pub use release_parser::*;
+mod season_rule;
+pub use season_rule::*;
 mod series;
 pub use series::*;
 mod regex_wrapper;
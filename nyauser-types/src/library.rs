@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LibraryImportRequest {
+    /// if true, nothing is written and the report shows what would be imported
+    #[serde(default)]
+    pub dry_run: bool,
+    /// only import this series, otherwise every series with a relocate directory
+    #[serde(default)]
+    pub series: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LibraryImportReport {
+    /// files that got (or would get) a new `Finished` pull entry
+    pub imported: Vec<LibraryFile>,
+    /// files for episodes that already have a pull entry
+    pub existing: Vec<LibraryFile>,
+    /// files the series' profile couldn't parse
+    pub unparsed: Vec<LibraryFile>,
+    /// series whose files couldn't be listed, i.e. for a missing profile
+    #[serde(default)]
+    pub skipped: Vec<LibrarySkippedSeries>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LibrarySkippedSeries {
+    pub series: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LibraryFile {
+    pub series: String,
+    pub path: String,
+    /// pull entry key for parsed files
+    #[serde(default)]
+    pub key: Option<String>,
+    /// parse error for unparsed files
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LibraryRescanReport {
+    /// entries whose files were found moved or renamed
+    pub relocated: Vec<RescannedEntry>,
+    /// entries whose files are gone
+    pub missing: Vec<RescannedEntry>,
+    /// previously missing entries whose files are back
+    pub restored: Vec<RescannedEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RescannedEntry {
+    pub key: String,
+    /// files as they are now, or the missing files
+    pub files: Vec<String>,
+}
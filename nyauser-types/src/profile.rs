@@ -1,43 +1,288 @@
+use anyhow::{anyhow, bail, Result};
 use regex::Match;
 use serde::{Deserialize, Serialize};
 
 use crate::regex_wrapper::RegexWrapper;
 
-use super::StandardEpisode;
+use super::{
+    apply_episode_rules, apply_season_rules, parse_release, RankRule, ReleaseFilter, Series,
+    StandardEpisode,
+};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Profile {
     pub name: String,
     /// initial parts of search phrase, of which is followed by space and series name
     pub search_prefix: Option<String>,
-    /// torrent name parsing regex
-    pub parse_regex: RegexWrapper,
+    /// how release names are parsed
+    #[serde(default)]
+    pub parser: ParserKind,
+    /// torrent name parsing regex, required for `parser: regex`. Captures named `resolution`,
+    /// `video_codec`, `audio_codec`, `source`, `version` or `language` fill the typed fields of
+    /// the same name and `group` fills `release_group`, any others go in `ext`
+    #[serde(default)]
+    pub parse_regex: Option<RegexWrapper>,
+    /// releases not passing are rejected
+    #[serde(default)]
+    pub filter: ReleaseFilter,
+    /// how releases of the same episode are ranked, most important first. The best is grabbed,
+    /// with the rest as fallbacks; without rules the first found wins.
+    #[serde(default)]
+    pub rank: Vec<RankRule>,
     /// if set, is a default path for series relocation. I.e. `relocate`/<series-name>/Season X/episode1.mp4
     pub relocate: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParserKind {
+    /// `parse_regex`, with `title`, `season`, `episode` and `checksum` captures
+    #[default]
+    Regex,
+    /// the built-in parser, for release groups without a hand-written regex
+    Auto,
+}
+
+/// A profile referenced by name, or supplied in full
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ProfileRef {
+    Named(String),
+    Inline(Box<Profile>),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProfileTestRequest {
+    pub profile: ProfileRef,
+    #[serde(default)]
+    pub titles: Vec<String>,
+    /// if set, titles returned by the configured source for this query are tested too
+    #[serde(default)]
+    pub query: Option<String>,
+    /// if set, titles are parsed as for this series, applying its season and episode rules
+    #[serde(default)]
+    pub series: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProfileTestResult {
+    pub title: String,
+    pub parsed: Option<StandardEpisode>,
+    /// why parsing failed, if it did
+    pub error: Option<String>,
+}
+
+/// CRC32 as written in release names, i.e. `A1B2C3D4`
+pub(crate) fn parse_checksum(value: &str) -> Option<u32> {
+    hex::decode(value)
+        .ok()
+        .and_then(|x| x.try_into().ok())
+        .map(u32::from_le_bytes)
+}
+
 impl Profile {
-    pub fn parse_name<'s, 't>(&'s self, name: &'t str) -> Option<StandardEpisode> {
-        let mut out = StandardEpisode::default();
-        out.season = 1;
-        let captures = self.parse_regex.captures(name)?;
-        for name in self.parse_regex.capture_names().filter_map(|x| x) {
+    /// Rejects settings that would fail every parse
+    pub fn validate(&self) -> Result<()> {
+        if self.parser == ParserKind::Regex && self.parse_regex.is_none() {
+            bail!(
+                "profile '{}' has no parse_regex, set one or use `parser: auto`",
+                self.name
+            );
+        }
+        Ok(())
+    }
+
+    /// `try_parse_name`, then the series' season rules on the title and episode rules on the result
+    pub fn parse_for_series(&self, name: &str, series: &Series) -> Result<StandardEpisode> {
+        let mut out = self.try_parse_name(name)?;
+        apply_season_rules(&series.season_rules, &mut out)?;
+        apply_episode_rules(&series.episode_rules, &mut out)?;
+        if series.specials_season_zero && out.episode.is_special() {
+            out.season = 0;
+        }
+        Ok(out)
+    }
+
+    /// Parses a release name with the profile's parser, reporting why parsing failed
+    pub fn try_parse_name(&self, name: &str) -> Result<StandardEpisode> {
+        match self.parser {
+            ParserKind::Regex => self.parse_with_regex(name),
+            ParserKind::Auto => parse_release(name).into_episode(),
+        }
+    }
+
+    fn parse_with_regex(&self, name: &str) -> Result<StandardEpisode> {
+        let parse_regex = self
+            .parse_regex
+            .as_ref()
+            .ok_or_else(|| anyhow!("profile has no parse_regex, set one or use `parser: auto`"))?;
+        let mut out = StandardEpisode {
+            season: 1,
+            ..Default::default()
+        };
+        let captures = parse_regex
+            .captures(name)
+            .ok_or_else(|| anyhow!("parse_regex did not match"))?;
+        for name in parse_regex.capture_names().flatten() {
             let value = match captures.name(name).as_ref().map(Match::as_str) {
                 Some(x) => x,
                 None => continue,
             };
             match name {
                 "title" => out.title = value.to_string(),
-                "season" => out.season = value.parse().ok()?,
-                "episode" => out.episode = value.parse().ok()?,
+                "season" => {
+                    out.season = value
+                        .parse()
+                        .map_err(|_| anyhow!("invalid season '{value}'"))?
+                }
+                "episode" => {
+                    out.episode = value
+                        .parse()
+                        .map_err(|_| anyhow!("invalid episode '{value}'"))?
+                }
                 "checksum" => {
-                    out.checksum = u32::from_le_bytes(hex::decode(value).ok()?.try_into().ok()?)
+                    out.checksum = parse_checksum(value)
+                        .ok_or_else(|| anyhow!("invalid checksum '{value}'"))?
                 }
                 name => {
-                    out.ext.insert(name.to_string(), value.to_string());
+                    if !out.set_attribute(name, value) {
+                        out.ext.insert(name.to_string(), value.to_string());
+                    }
                 }
             }
         }
-        Some(out)
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Episode, RegexWrapper};
+    use regex::Regex;
+
+    fn profile() -> Profile {
+        Profile {
+            name: "subsplease".to_string(),
+            search_prefix: None,
+            parser: ParserKind::Regex,
+            parse_regex: Some(RegexWrapper(Regex::new(r"\[SubsPlease\] (?P<title>.*?) (?:S(?P<season>[0-9]{1,2}) )?- (?P<episode>(?:SP)?[0-9]{1,3}(?:\.\d)?)(?:v[0-9])? \((?P<resolution>1080p)\) \[(?P<checksum>[0-9a-zA-Z]{8})\]\.mkv").unwrap())),
+            filter: ReleaseFilter::default(),
+            rank: vec![],
+            relocate: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_name() {
+        let parsed = profile()
+            .try_parse_name("[SubsPlease] Spy x Family S2 - 05 (1080p) [A1B2C3D4].mkv")
+            .unwrap();
+        assert_eq!(parsed.title, "Spy x Family");
+        assert_eq!(parsed.season, 2);
+        assert_eq!(parsed.episode, Episode::Standard(5));
+        assert_eq!(
+            parsed.checksum,
+            u32::from_le_bytes([0xA1, 0xB2, 0xC3, 0xD4])
+        );
+        assert_eq!(parsed.resolution, Some(1080));
+        assert!(parsed.ext.is_empty());
+    }
+
+    #[test]
+    fn test_parse_name_untyped_attributes() {
+        let profile = Profile {
+            parse_regex: Some(RegexWrapper(Regex::new(r"\[(?P<source>\w+)\] (?P<title>.*?) - (?P<episode>\d+)(?P<version>\w*) \((?P<resolution>\w+)\)").unwrap())),
+            ..profile()
+        };
+        let parsed = profile
+            .try_parse_name("[CR] Spy x Family - 05final (FHD)")
+            .unwrap();
+        assert_eq!(parsed.episode, Episode::Standard(5));
+        assert_eq!(parsed.source, None);
+        assert_eq!(parsed.version, None);
+        assert_eq!(parsed.resolution, None);
+        assert_eq!(parsed.ext.get("source").map(String::as_str), Some("CR"));
+        assert_eq!(parsed.ext.get("version").map(String::as_str), Some("final"));
+        assert_eq!(
+            parsed.ext.get("resolution").map(String::as_str),
+            Some("FHD")
+        );
+    }
+
+    #[test]
+    fn test_parse_name_errors() {
+        let profile = profile();
+        let error = profile
+            .try_parse_name("[Erai-raws] Spy x Family - 05 [1080p].mkv")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "parse_regex did not match");
+        let error = profile
+            .try_parse_name("[SubsPlease] Spy x Family - 05 (1080p) [ZZZZZZZZ].mkv")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "invalid checksum 'ZZZZZZZZ'");
+    }
+
+    #[test]
+    fn test_parse_name_auto() {
+        let profile = Profile {
+            parser: ParserKind::Auto,
+            parse_regex: None,
+            ..profile()
+        };
+        let parsed = profile
+            .try_parse_name(
+                "[Erai-raws] Spy x Family S2 - 05v2 [1080p][Multiple Subtitle][A1B2C3D4].mkv",
+            )
+            .unwrap();
+        assert_eq!(parsed.title, "Spy x Family");
+        assert_eq!(parsed.season, 2);
+        assert_eq!(parsed.episode, Episode::Standard(5));
+        assert_eq!(
+            parsed.checksum,
+            u32::from_le_bytes([0xA1, 0xB2, 0xC3, 0xD4])
+        );
+        assert_eq!(parsed.release_group.as_deref(), Some("Erai-raws"));
+        assert_eq!(parsed.version, Some(2));
+        assert_eq!(parsed.resolution, Some(1080));
+        assert_eq!(parsed.language.as_deref(), Some("multi"));
+        assert_eq!(parsed.ext.get("extension").map(String::as_str), Some("mkv"));
+
+        let error = profile
+            .try_parse_name("[Group] Title - 01-12 (BD 1080p)")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "episodes 1-12 are a batch");
+        let error = profile
+            .try_parse_name("[Group] Title [BD 1080p]")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "no episode found");
+        let error = Profile {
+            parser: ParserKind::Regex,
+            ..profile
+        }
+        .try_parse_name("[Group] Title - 01")
+        .unwrap_err();
+        assert!(error.to_string().starts_with("profile has no parse_regex"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(profile().validate().is_ok());
+        let auto = Profile {
+            parser: ParserKind::Auto,
+            parse_regex: None,
+            ..profile()
+        };
+        assert!(auto.validate().is_ok());
+        let error = Profile {
+            parser: ParserKind::Regex,
+            ..auto
+        }
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "profile 'subsplease' has no parse_regex, set one or use `parser: auto`"
+        );
     }
 }
@@ -6,11 +6,16 @@ use std::{
     str::FromStr,
 };
 
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, Utc};
 use serde::{Deserialize, Serialize};
 
 use anyhow::Result;
 
+use super::{
+    normalize_audio_codec, normalize_language, normalize_video_codec, parse_resolution,
+    ReleaseSource,
+};
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PullEntryFilter {
     pub profile: Option<String>,
@@ -31,41 +36,135 @@ pub struct SearchResult {
     pub leechers: u64,
     pub downloads: u64,
     pub size: u64,
+    /// torrent info-hash, if the source provides it
+    #[serde(default)]
+    pub info_hash: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-#[serde(untagged)]
+/// Numbered episodes serialize as numbers, everything else as its display string
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Episode {
     Standard(u32),
-    Special(String),
+    /// i.e. `12.5`, as the episode and thousandths
+    Decimal(u32, u32),
+    /// `SP`, `SP1`
+    Special(Option<u32>),
+    /// `OVA`, `OVA2`, `OAD`
+    Ova(Option<u32>),
+    /// `Movie`, `Movie 2`
+    Movie(Option<u32>),
+    /// anything else, as released
+    Other(String),
+}
+
+impl Episode {
+    /// true for episodes outside the regular numbering, which may be put in season 0
+    pub fn is_special(&self) -> bool {
+        !matches!(self, Episode::Standard(_))
+    }
+
+    /// position among episodes: decimal episodes sort between their neighbours,
+    /// then specials, OVAs, movies and anything else
+    fn rank(&self) -> (u8, u64) {
+        match self {
+            Episode::Standard(x) => (0, *x as u64 * 1000),
+            Episode::Decimal(x, fraction) => (0, *x as u64 * 1000 + *fraction as u64),
+            Episode::Special(x) => (1, x.map_or(0, |x| x as u64 + 1)),
+            Episode::Ova(x) => (2, x.map_or(0, |x| x as u64 + 1)),
+            Episode::Movie(x) => (3, x.map_or(0, |x| x as u64 + 1)),
+            Episode::Other(_) => (4, 0),
+        }
+    }
+
+    /// the episode part of a pull entry key
+    pub fn key(&self) -> String {
+        let numbered = |prefix: &str, x: &Option<u32>| match x {
+            Some(x) => format!("{prefix}{x:02}"),
+            None => prefix.to_string(),
+        };
+        match self {
+            Episode::Standard(x) => format!("E{x:02}"),
+            Episode::Decimal(x, _) => {
+                let shown = self.to_string();
+                let (_, fraction) = shown.split_once('.').unwrap_or_default();
+                format!("E{x:02}.{fraction}")
+            }
+            Episode::Special(x) => numbered("SP", x),
+            Episode::Ova(x) => numbered("OVA", x),
+            Episode::Movie(x) => numbered("MOVIE", x),
+            Episode::Other(x) => format!("X{x}"),
+        }
+    }
 }
 
 impl PartialOrd for Episode {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match (self, other) {
-            (Episode::Standard(e1), Episode::Standard(e2)) => e1.partial_cmp(e2),
-            (Episode::Standard(_), Episode::Special(_)) => Some(Ordering::Less),
-            (Episode::Special(_), Episode::Standard(_)) => Some(Ordering::Greater),
-            (Episode::Special(e1), Episode::Special(e2)) => e1.partial_cmp(e2),
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Episode {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.rank()
+            .cmp(&other.rank())
+            .then_with(|| match (self, other) {
+                (Episode::Other(e1), Episode::Other(e2)) => e1.cmp(e2),
+                _ => Ordering::Equal,
+            })
     }
 }
 
+/// `prefix`, optionally followed by spaces and a number
+fn parse_numbered(value: &str, prefixes: &[&str]) -> Option<Option<u32>> {
+    prefixes.iter().find_map(|prefix| {
+        let rest = value
+            .get(..prefix.len())
+            .filter(|x| x.eq_ignore_ascii_case(prefix))
+            .map(|_| value[prefix.len()..].trim_start())?;
+        if rest.is_empty() {
+            Some(None)
+        } else {
+            rest.parse().ok().map(Some)
+        }
+    })
+}
+
+/// `12.5` -> (12, 500)
+fn parse_decimal(value: &str) -> Option<(u32, u32)> {
+    let (whole, fraction) = value.split_once('.')?;
+    if fraction.is_empty() || fraction.len() > 3 || !fraction.bytes().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    let scale = 10u32.pow(3 - fraction.len() as u32);
+    Some((whole.parse().ok()?, fraction.parse::<u32>().ok()? * scale))
+}
+
 impl FromStr for Episode {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some(s) = s.parse().ok() {
-            Ok(Episode::Standard(s))
-        } else {
-            Ok(Episode::Special(s.to_string()))
+        let s = s.trim();
+        if let Ok(x) = s.parse() {
+            return Ok(Episode::Standard(x));
         }
+        if let Some((x, fraction)) = parse_decimal(s) {
+            return Ok(if fraction == 0 {
+                Episode::Standard(x)
+            } else {
+                Episode::Decimal(x, fraction)
+            });
+        }
+        // longer prefixes first, so `Special` isn't read as `SP` followed by garbage
+        if let Some(x) = parse_numbered(s, &["Special", "SP"]) {
+            return Ok(Episode::Special(x));
+        }
+        if let Some(x) = parse_numbered(s, &["OVA", "OAD"]) {
+            return Ok(Episode::Ova(x));
+        }
+        if let Some(x) = parse_numbered(s, &["Movie"]) {
+            return Ok(Episode::Movie(x));
+        }
+        Ok(Episode::Other(s.to_string()))
     }
 }
 
@@ -77,22 +176,131 @@ impl Default for Episode {
 
 impl fmt::Display for Episode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let numbered = |f: &mut fmt::Formatter<'_>, prefix: &str, x: &Option<u32>| match x {
+            Some(x) => write!(f, "{prefix}{x}"),
+            None => write!(f, "{prefix}"),
+        };
         match self {
             Episode::Standard(x) => write!(f, "{}", x),
-            Episode::Special(x) => write!(f, "{}", x),
+            Episode::Decimal(x, fraction) => {
+                let fraction = format!("{fraction:03}");
+                write!(f, "{}.{}", x, fraction.trim_end_matches('0'))
+            }
+            Episode::Special(x) => numbered(f, "SP", x),
+            Episode::Ova(x) => numbered(f, "OVA", x),
+            Episode::Movie(Some(x)) => write!(f, "Movie {x}"),
+            Episode::Movie(None) => write!(f, "Movie"),
+            Episode::Other(x) => write!(f, "{}", x),
         }
     }
 }
 
+impl Serialize for Episode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Episode::Standard(x) => serializer.serialize_u32(*x),
+            x => serializer.collect_str(x),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Episode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Episode;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an episode number or string")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Episode, E> {
+                u32::try_from(value)
+                    .map(Episode::Standard)
+                    .map_err(|_| E::custom(format!("episode {value} out of range")))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<Episode, E> {
+                u64::try_from(value)
+                    .map_err(|_| E::custom(format!("negative episode {value}")))
+                    .and_then(|x| self.visit_u64(x))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Episode, E> {
+                value
+                    .parse()
+                    .map_err(|_| E::custom(format!("invalid episode '{value}'")))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct StandardEpisode {
     pub title: String,
     pub season: u32,
     pub episode: Episode,
     pub checksum: u32,
+    /// vertical resolution, i.e. 1080
+    #[serde(default)]
+    pub resolution: Option<u32>,
+    /// normalized, i.e. `h265` for `HEVC` or `x265`
+    #[serde(default)]
+    pub video_codec: Option<String>,
+    /// normalized, i.e. `eac3` for `DDP`
+    #[serde(default)]
+    pub audio_codec: Option<String>,
+    #[serde(default)]
+    pub source: Option<ReleaseSource>,
+    #[serde(default)]
+    pub release_group: Option<String>,
+    /// release revision, i.e. 2 of `05v2`
+    #[serde(default)]
+    pub version: Option<u32>,
+    /// two letter code where known, i.e. `en`, or `multi`
+    #[serde(default)]
+    pub language: Option<String>,
+    /// captures without a field of their own
     pub ext: HashMap<String, String>,
 }
 
+impl StandardEpisode {
+    /// Sets the field for a well-known capture name, returning false for other names and for
+    /// values that don't fit the field, like a `CR` source, which belong in `ext`.
+    /// Values that describe but don't name a codec or language, like `10bit`, are dropped.
+    pub fn set_attribute(&mut self, name: &str, value: &str) -> bool {
+        match name {
+            "resolution" => match parse_resolution(value) {
+                Some(x) => self.resolution = Some(x),
+                None => return false,
+            },
+            "video_codec" => self.video_codec = normalize_video_codec(value),
+            "audio_codec" => self.audio_codec = normalize_audio_codec(value),
+            "source" => match value.parse() {
+                Ok(x) => self.source = Some(x),
+                Err(_) => return false,
+            },
+            "group" | "release_group" => self.release_group = Some(value.to_string()),
+            "version" => match value.trim_start_matches(['v', 'V']).parse() {
+                Ok(x) => self.version = Some(x),
+                Err(_) => return false,
+            },
+            "language" => self.language = normalize_language(value),
+            _ => return false,
+        }
+        true
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct PullEntryNamed {
     pub id: String,
@@ -108,38 +316,216 @@ pub struct PullEntry {
     pub state: PullState,
     #[serde(default)]
     pub files: Vec<String>,
+    /// every state this entry has been in, oldest first
+    #[serde(default)]
+    pub transitions: Vec<PullTransition>,
+    /// set while pushing to the sink is failing
+    #[serde(default)]
+    pub retry: Option<RetryState>,
+    /// latest progress reported by the sink while downloading
+    #[serde(default)]
+    pub progress: Option<DownloadProgress>,
+    /// size in bytes of each of `files`, used to find them again if moved
+    #[serde(default)]
+    pub file_sizes: HashMap<String, u64>,
+    /// files that already existed at the relocation destination, and what was done about it
+    #[serde(default)]
+    pub conflicts: Vec<FileConflict>,
 }
 
 impl PullEntry {
+    pub fn new(result: ParsedSearchResult, state: PullState, reason: Option<String>) -> Self {
+        let mut out = Self {
+            result,
+            torrent_id: None,
+            torrent_hash: String::new(),
+            state,
+            files: vec![],
+            transitions: vec![],
+            retry: None,
+            progress: None,
+            file_sizes: HashMap::new(),
+            conflicts: vec![],
+        };
+        out.transition(state, reason);
+        out
+    }
+
     pub fn key(&self) -> String {
         self.result.key()
     }
+
+    /// moves to `state`, recording when and why
+    pub fn transition(&mut self, state: PullState, reason: Option<String>) {
+        self.state = state;
+        self.transitions.push(PullTransition {
+            state,
+            at: Utc::now(),
+            reason,
+        });
+    }
+
+    /// the transition into the current state, if recorded
+    pub fn last_transition(&self) -> Option<&PullTransition> {
+        self.transitions.last().filter(|x| x.state == self.state)
+    }
+
+    /// why the entry is in its current state, i.e. why it failed
+    pub fn reason(&self) -> Option<&str> {
+        self.last_transition()?.reason.as_deref()
+    }
+
+    /// true if a new release may be grabbed in place of this entry
+    pub fn is_replaceable(&self) -> bool {
+        self.state == PullState::Failed && self.torrent_id.is_none() && self.retry.is_none()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DownloadProgress {
+    /// 0.0 to 1.0
+    pub percent_done: f32,
+    pub peers: u64,
+    /// bytes per second
+    pub rate_download: u64,
+    /// seconds until done, if the sink can estimate it
+    #[serde(default)]
+    pub eta_seconds: Option<u64>,
+    /// bytes to download
+    #[serde(default)]
+    pub size: u64,
+    /// last time `percent_done` increased
+    pub last_progress: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RetryState {
+    /// failed pushes so far
+    pub attempts: u32,
+    /// None on entries that gave up under older versions, which kept the retry state. These
+    /// must be deleted to try again.
+    pub next_attempt: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileConflict {
+    /// relocated file, relative to the relocate directory
+    pub file: String,
+    #[serde(flatten)]
+    pub outcome: ConflictOutcome,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ConflictOutcome {
+    /// the existing file was kept, the new one discarded
+    Skipped,
+    /// the existing file was moved to `recycled`
+    Overwritten { recycled: String },
+    /// the new file was stored as `renamed` next to the existing one
+    KeptBoth { renamed: String },
+    /// the new file was better, the existing file was moved to `recycled`
+    Replaced { recycled: String },
+    /// the existing file was at least as good, the new one discarded
+    KeptExisting,
+}
+
+impl fmt::Display for ConflictOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictOutcome::Skipped => write!(f, "skipped"),
+            ConflictOutcome::Overwritten { recycled } => {
+                write!(f, "overwritten, old file recycled to {recycled}")
+            }
+            ConflictOutcome::KeptBoth { renamed } => write!(f, "kept both, new file is {renamed}"),
+            ConflictOutcome::Replaced { recycled } => {
+                write!(f, "replaced, old file recycled to {recycled}")
+            }
+            ConflictOutcome::KeptExisting => write!(f, "kept existing"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PullTransition {
+    pub state: PullState,
+    pub at: DateTime<Utc>,
+    #[serde(default)]
+    pub reason: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum PullState {
+    /// waiting for a download slot
+    Queued,
     Downloading,
+    /// download finished, files are being relocated
+    Importing,
+    /// files were relocated into the library
+    Imported,
+    /// download finished with nothing to relocate
     Finished,
+    /// see `PullEntry::reason`
+    Failed,
+    /// unwanted, never grabbed
+    Ignored,
+    /// files were found moved or renamed by a library rescan
+    Relocated,
+    /// files are gone from the library
+    Missing,
 }
 
-impl fmt::Display for PullState {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl PullState {
+    pub const ALL: [PullState; 9] = [
+        PullState::Queued,
+        PullState::Downloading,
+        PullState::Importing,
+        PullState::Imported,
+        PullState::Finished,
+        PullState::Failed,
+        PullState::Ignored,
+        PullState::Relocated,
+        PullState::Missing,
+    ];
+
+    /// true if the episode's files are in place
+    pub fn is_complete(&self) -> bool {
+        matches!(
+            self,
+            PullState::Imported | PullState::Finished | PullState::Relocated
+        )
+    }
+
+    pub fn as_str(&self) -> &'static str {
         match self {
-            PullState::Downloading => write!(f, "downloading"),
-            PullState::Finished => write!(f, "finished"),
+            PullState::Queued => "queued",
+            PullState::Downloading => "downloading",
+            PullState::Importing => "importing",
+            PullState::Imported => "imported",
+            PullState::Finished => "finished",
+            PullState::Failed => "failed",
+            PullState::Ignored => "ignored",
+            PullState::Relocated => "relocated",
+            PullState::Missing => "missing",
         }
     }
 }
 
+impl fmt::Display for PullState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 impl FromStr for PullState {
     type Err = ();
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s {
-            "downloading" => Ok(PullState::Downloading),
-            "finished" => Ok(PullState::Finished),
-            _ => Err(()),
-        }
+        PullState::ALL
+            .into_iter()
+            .find(|state| state.as_str() == s)
+            .ok_or(())
     }
 }
 
@@ -148,15 +534,22 @@ pub struct ParsedSearchResult {
     pub result: SearchResult,
     pub parsed: StandardEpisode,
     pub profile: String,
+    /// name of the series this result was found for
+    #[serde(default)]
+    pub series: Option<String>,
     pub relocate: Option<String>,
     pub relocate_season: bool,
 }
 
 impl ParsedSearchResult {
+    /// Namespaced by the owning series, so releases titled differently land on the same episode.
+    /// Results without a series (from before they were recorded) fall back to the parsed title.
     pub fn key(&self) -> String {
         format!(
-            "{}_S{:02}E{:02}",
-            self.parsed.title, self.parsed.season, self.parsed.episode
+            "{}_S{:02}{}",
+            self.series.as_deref().unwrap_or(&self.parsed.title),
+            self.parsed.season,
+            self.parsed.episode.key()
         )
     }
 
@@ -176,7 +569,69 @@ mod tests {
     #[test]
     fn test_episode_order() {
         assert!(Episode::Standard(5) < Episode::Standard(10));
-        assert!(Episode::Standard(15) < Episode::Special("test".to_string()));
-        assert!(Episode::Special("2".to_string()) < Episode::Special("20".to_string()));
+        assert!(Episode::Standard(12) < Episode::Decimal(12, 500));
+        assert!(Episode::Decimal(12, 500) < Episode::Standard(13));
+        assert!(Episode::Standard(15) < Episode::Special(None));
+        assert!(Episode::Special(Some(2)) < Episode::Special(Some(10)));
+        assert!(Episode::Special(Some(10)) < Episode::Ova(Some(1)));
+        assert!(Episode::Ova(Some(1)) < Episode::Movie(None));
+        assert!(Episode::Movie(Some(1)) < Episode::Other("Recap".to_string()));
+    }
+
+    #[test]
+    fn test_episode_parse() {
+        let parse = |x: &str| x.parse::<Episode>().unwrap();
+        assert_eq!(parse("07"), Episode::Standard(7));
+        assert_eq!(parse("12.5"), Episode::Decimal(12, 500));
+        assert_eq!(parse("12.0"), Episode::Standard(12));
+        assert_eq!(parse("SP1"), Episode::Special(Some(1)));
+        assert_eq!(parse("sp"), Episode::Special(None));
+        assert_eq!(parse("Special 2"), Episode::Special(Some(2)));
+        assert_eq!(parse("OVA2"), Episode::Ova(Some(2)));
+        assert_eq!(parse("OAD"), Episode::Ova(None));
+        assert_eq!(parse("Movie 3"), Episode::Movie(Some(3)));
+        assert_eq!(parse("Recap"), Episode::Other("Recap".to_string()));
+        for episode in [
+            "7", "12.5", "12.25", "SP1", "SP", "OVA2", "Movie 3", "Movie", "Recap",
+        ] {
+            assert_eq!(parse(episode).to_string(), episode);
+        }
+    }
+
+    #[test]
+    fn test_episode_serde() {
+        let episodes = [
+            Episode::Standard(7),
+            Episode::Decimal(12, 500),
+            Episode::Special(Some(1)),
+            Episode::Movie(None),
+        ];
+        let raw = serde_json::to_string(&episodes).unwrap();
+        assert_eq!(raw, r#"[7,"12.5","SP1","Movie"]"#);
+        assert_eq!(
+            serde_json::from_str::<Vec<Episode>>(&raw).unwrap(),
+            episodes
+        );
+        // map keys are always strings
+        let map: std::collections::BTreeMap<Episode, u32> =
+            serde_json::from_str(r#"{"7":1,"SP1":2}"#).unwrap();
+        assert_eq!(map.keys().next(), Some(&Episode::Standard(7)));
+    }
+
+    #[test]
+    fn test_episode_key() {
+        assert_eq!(Episode::Standard(7).key(), "E07");
+        assert_eq!(Episode::Standard(1080).key(), "E1080");
+        assert_eq!(Episode::Decimal(7, 500).key(), "E07.5");
+        assert_eq!(Episode::Special(Some(1)).key(), "SP01");
+        assert_eq!(Episode::Ova(None).key(), "OVA");
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        for state in PullState::ALL {
+            assert_eq!(state.to_string().parse(), Ok(state));
+        }
+        assert_eq!("unknown".parse::<PullState>(), Err(()));
     }
 }
@@ -13,6 +13,8 @@ impl PartialEq for RegexWrapper {
     }
 }
 
+impl Eq for RegexWrapper {}
+
 impl Serialize for RegexWrapper {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -0,0 +1,375 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::StandardEpisode;
+
+/// What a release was ripped from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseSource {
+    Web,
+    Bd,
+    Tv,
+    Dvd,
+}
+
+impl FromStr for ReleaseSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let folded = s.to_ascii_uppercase().replace(['-', '_', ' '], "");
+        Ok(match &*folded {
+            "WEB" | "WEBDL" | "WEBRIP" => ReleaseSource::Web,
+            "BD" | "BDRIP" | "BDREMUX" | "BDMV" | "BLURAY" | "REMUX" => ReleaseSource::Bd,
+            "TV" | "TVRIP" | "HDTV" | "HDTVRIP" => ReleaseSource::Tv,
+            "DVD" | "DVDRIP" | "DVD5" | "DVD9" => ReleaseSource::Dvd,
+            _ => return Err(anyhow!("unknown source '{s}'")),
+        })
+    }
+}
+
+impl fmt::Display for ReleaseSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReleaseSource::Web => write!(f, "WEB"),
+            ReleaseSource::Bd => write!(f, "BD"),
+            ReleaseSource::Tv => write!(f, "TV"),
+            ReleaseSource::Dvd => write!(f, "DVD"),
+        }
+    }
+}
+
+/// Vertical resolution of `1080p`, `1080`, `1920x1080` or `4K`
+pub fn parse_resolution(value: &str) -> Option<u32> {
+    let value = value.trim().to_ascii_lowercase();
+    match &*value {
+        "4k" => return Some(2160),
+        "8k" => return Some(4320),
+        _ => (),
+    }
+    let value = value.rsplit_once('x').map(|(_, x)| x).unwrap_or(&value);
+    value
+        .trim_end_matches(['p', 'i'])
+        .parse()
+        .ok()
+        .filter(|x| *x > 0)
+}
+
+/// `h264`, `h265`, `av1` and so on for the names codecs are written under.
+/// None for words that describe the video but aren't a codec, like `10bit` or `HDR`
+pub fn normalize_video_codec(value: &str) -> Option<String> {
+    let folded = value.to_ascii_uppercase().replace(['.', '-', '_'], "");
+    let normalized = match &*folded {
+        "X264" | "H264" | "AVC" => "h264",
+        "X265" | "H265" | "HEVC" => "h265",
+        "10BIT" | "10BITS" | "8BIT" | "HI10" | "HI10P" | "HI444" | "HDR" | "HDR10" | "SDR" => {
+            return None
+        }
+        _ => return Some(value.to_lowercase()),
+    };
+    Some(normalized.to_string())
+}
+
+/// `aac`, `flac`, `eac3` and so on for the names codecs are written under.
+/// None for words that describe the audio but aren't a codec, like `5.1` or `Dual-Audio`
+pub fn normalize_audio_codec(value: &str) -> Option<String> {
+    let folded = value.to_ascii_uppercase().replace(['-', '_', ' '], "");
+    let normalized = match &*folded {
+        "EAC3" | "DDP" => "eac3",
+        "AC3" | "DD" => "ac3",
+        "DTSHD" | "DTSHDMA" => "dts-hd",
+        "LPCM" | "PCM" => "pcm",
+        "2.0" | "5.1" | "7.1" | "2CH" | "6CH" | "DUALAUDIO" | "MULTIAUDIO" => return None,
+        _ => return Some(value.to_lowercase()),
+    };
+    Some(normalized.to_string())
+}
+
+/// Two letter codes for languages written out or abbreviated, `multi` for multiple.
+/// None for words that don't name a language, like `Subbed`
+pub fn normalize_language(value: &str) -> Option<String> {
+    let folded = value.to_ascii_uppercase().replace(['-', '_', ' '], "");
+    let normalized = match &*folded {
+        "ENG" | "ENGLISH" | "ENGSUB" | "ESUB" | "ESUBS" | "ENGLISHSUB" | "ENGLISHDUB" => "en",
+        "JPN" | "JAP" | "JAPANESE" | "RAW" => "ja",
+        "GER" | "GERMAN" | "DEU" => "de",
+        "FRE" | "FRENCH" | "FRA" | "VOSTFR" => "fr",
+        "SPA" | "SPANISH" => "es",
+        "ITA" | "ITALIAN" => "it",
+        "POR" => "pt",
+        "RUS" => "ru",
+        "CHS" | "CHT" | "CHI" => "zh",
+        "KOR" => "ko",
+        "MULTI" | "MULTISUB" | "MULTISUBS" | "MULTIPLESUBTITLE" | "MULTIPLESUBTITLES" => "multi",
+        "SUBBED" | "DUBBED" => return None,
+        _ => return Some(value.to_lowercase()),
+    };
+    Some(normalized.to_string())
+}
+
+/// where `value` is in `order`, comparing both normalized with `normalize`
+fn position(order: &[String], value: &str, normalize: fn(&str) -> Option<String>) -> Option<usize> {
+    let value = normalize(value).unwrap_or_else(|| value.to_lowercase());
+    order
+        .iter()
+        .position(|x| normalize(x).unwrap_or_else(|| x.to_lowercase()) == value)
+}
+
+/// release groups are compared ignoring case
+fn fold_group(value: &str) -> Option<String> {
+    Some(value.to_lowercase())
+}
+
+/// Releases a profile accepts. Releases that don't state an attribute are not filtered on it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReleaseFilter {
+    /// i.e. 1080 to reject 720p releases
+    #[serde(default)]
+    pub min_resolution: Option<u32>,
+    #[serde(default)]
+    pub max_resolution: Option<u32>,
+    /// if not empty, the only sources accepted
+    #[serde(default)]
+    pub sources: Vec<ReleaseSource>,
+    /// if not empty, the only video codecs accepted, i.e. `hevc` or `x265` for h265
+    #[serde(default)]
+    pub video_codecs: Vec<String>,
+    /// if not empty, the only audio codecs accepted
+    #[serde(default)]
+    pub audio_codecs: Vec<String>,
+    /// if not empty, the only languages accepted, i.e. `en` or `eng`
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// if not empty, the only release groups accepted
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+impl ReleaseFilter {
+    /// why `parsed` is rejected, if it is
+    pub fn rejects(&self, parsed: &StandardEpisode) -> Option<String> {
+        if let Some(resolution) = parsed.resolution {
+            if self.min_resolution.map_or(false, |x| resolution < x) {
+                return Some(format!("resolution {resolution}p is too low"));
+            }
+            if self.max_resolution.map_or(false, |x| resolution > x) {
+                return Some(format!("resolution {resolution}p is too high"));
+            }
+        }
+        if let Some(source) = parsed.source {
+            if !self.sources.is_empty() && !self.sources.contains(&source) {
+                return Some(format!("source {source} is not allowed"));
+            }
+        }
+        let lists = [
+            (
+                "video codec",
+                &self.video_codecs,
+                &parsed.video_codec,
+                normalize_video_codec as fn(&str) -> Option<String>,
+            ),
+            (
+                "audio codec",
+                &self.audio_codecs,
+                &parsed.audio_codec,
+                normalize_audio_codec,
+            ),
+            (
+                "language",
+                &self.languages,
+                &parsed.language,
+                normalize_language,
+            ),
+            ("group", &self.groups, &parsed.release_group, fold_group),
+        ];
+        for (name, allowed, value, normalize) in lists {
+            let Some(value) = value else {
+                continue;
+            };
+            if !allowed.is_empty() && position(allowed, value, normalize).is_none() {
+                return Some(format!("{name} {value} is not allowed"));
+            }
+        }
+        None
+    }
+}
+
+/// One criterion releases of the same episode are ranked by, to pick the one grabbed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RankRule {
+    /// higher resolutions first
+    Resolution,
+    /// higher versions first, so a v2 beats the release it fixes
+    Version,
+    /// in the order listed, then unlisted ones. The same goes for the rules below.
+    Source {
+        order: Vec<ReleaseSource>,
+    },
+    VideoCodec {
+        order: Vec<String>,
+    },
+    AudioCodec {
+        order: Vec<String>,
+    },
+    Language {
+        order: Vec<String>,
+    },
+    Group {
+        order: Vec<String>,
+    },
+}
+
+impl RankRule {
+    /// lower is better
+    fn score(&self, parsed: &StandardEpisode) -> i64 {
+        let ordered = |order: &[String], value: &Option<String>, normalize| {
+            value
+                .as_deref()
+                .and_then(|x| position(order, x, normalize))
+                .unwrap_or(order.len()) as i64
+        };
+        match self {
+            RankRule::Resolution => -(parsed.resolution.unwrap_or(0) as i64),
+            RankRule::Version => -(parsed.version.unwrap_or(1) as i64),
+            RankRule::Source { order } => parsed
+                .source
+                .and_then(|x| order.iter().position(|y| *y == x))
+                .unwrap_or(order.len()) as i64,
+            RankRule::VideoCodec { order } => {
+                ordered(order, &parsed.video_codec, normalize_video_codec)
+            }
+            RankRule::AudioCodec { order } => {
+                ordered(order, &parsed.audio_codec, normalize_audio_codec)
+            }
+            RankRule::Language { order } => ordered(order, &parsed.language, normalize_language),
+            RankRule::Group { order } => ordered(order, &parsed.release_group, fold_group),
+        }
+    }
+}
+
+/// Sort key of a release under `rules`, most important rule first. Lower is better.
+pub fn rank_key(rules: &[RankRule], parsed: &StandardEpisode) -> Vec<i64> {
+    rules.iter().map(|x| x.score(parsed)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(
+        resolution: u32,
+        source: ReleaseSource,
+        codec: &str,
+        group: &str,
+    ) -> StandardEpisode {
+        StandardEpisode {
+            resolution: Some(resolution),
+            source: Some(source),
+            video_codec: normalize_video_codec(codec),
+            release_group: Some(group.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(parse_resolution("1080p"), Some(1080));
+        assert_eq!(parse_resolution("1920x1080"), Some(1080));
+        assert_eq!(parse_resolution("4K"), Some(2160));
+        assert_eq!(parse_resolution("HD"), None);
+        assert_eq!(
+            "WEB-DL".parse::<ReleaseSource>().unwrap(),
+            ReleaseSource::Web
+        );
+        assert_eq!(
+            "Blu-Ray".parse::<ReleaseSource>().unwrap(),
+            ReleaseSource::Bd
+        );
+        assert!("VHS".parse::<ReleaseSource>().is_err());
+        assert_eq!(normalize_video_codec("HEVC").as_deref(), Some("h265"));
+        assert_eq!(normalize_video_codec("x265").as_deref(), Some("h265"));
+        assert_eq!(normalize_video_codec("10bit"), None);
+        assert_eq!(normalize_audio_codec("E-AC-3").as_deref(), Some("eac3"));
+        assert_eq!(normalize_audio_codec("Dual-Audio"), None);
+        assert_eq!(normalize_language("ENG").as_deref(), Some("en"));
+        assert_eq!(normalize_language("Multi-Subs").as_deref(), Some("multi"));
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = ReleaseFilter {
+            min_resolution: Some(1080),
+            sources: vec![ReleaseSource::Web],
+            video_codecs: vec!["hevc".to_string()],
+            groups: vec!["subsplease".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.rejects(&release(1080, ReleaseSource::Web, "x265", "SubsPlease")),
+            None
+        );
+        assert_eq!(
+            filter.rejects(&release(720, ReleaseSource::Web, "x265", "SubsPlease")),
+            Some("resolution 720p is too low".to_string())
+        );
+        assert_eq!(
+            filter.rejects(&release(1080, ReleaseSource::Bd, "x265", "SubsPlease")),
+            Some("source BD is not allowed".to_string())
+        );
+        assert_eq!(
+            filter.rejects(&release(1080, ReleaseSource::Web, "AVC", "SubsPlease")),
+            Some("video codec h264 is not allowed".to_string())
+        );
+        assert_eq!(
+            filter.rejects(&release(1080, ReleaseSource::Web, "x265", "Erai-raws")),
+            Some("group Erai-raws is not allowed".to_string())
+        );
+        // unstated attributes pass
+        assert_eq!(filter.rejects(&StandardEpisode::default()), None);
+    }
+
+    #[test]
+    fn test_rank() {
+        let rules = [
+            RankRule::Resolution,
+            RankRule::Group {
+                order: vec!["SubsPlease".to_string(), "Erai-raws".to_string()],
+            },
+            RankRule::Version,
+        ];
+        let mut releases = [
+            release(720, ReleaseSource::Web, "x264", "SubsPlease"),
+            release(1080, ReleaseSource::Web, "x264", "Other"),
+            release(1080, ReleaseSource::Web, "x264", "Erai-raws"),
+            StandardEpisode {
+                version: Some(2),
+                ..release(1080, ReleaseSource::Web, "x264", "subsplease")
+            },
+            release(1080, ReleaseSource::Web, "x264", "SubsPlease"),
+        ];
+        releases.sort_by_cached_key(|x| rank_key(&rules, x));
+        let ranked = releases
+            .iter()
+            .map(|x| {
+                (
+                    x.resolution.unwrap(),
+                    x.release_group.clone().unwrap(),
+                    x.version,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranked,
+            vec![
+                (1080, "subsplease".to_string(), Some(2)),
+                (1080, "SubsPlease".to_string(), None),
+                (1080, "Erai-raws".to_string(), None),
+                (1080, "Other".to_string(), None),
+                (720, "SubsPlease".to_string(), None),
+            ]
+        );
+    }
+}
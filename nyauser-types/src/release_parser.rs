@@ -0,0 +1,1089 @@
+//! Anitomy-style parsing of arbitrary release names, for profiles with `parser: auto`.
+//!
+//! Names are split into bracketed and free text, and words are recognized by position and by
+//! keyword. Anything not recognized in the free text before the episode is the title.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    normalize_audio_codec, normalize_language, normalize_video_codec, parse_resolution,
+    profile::parse_checksum, Episode, StandardEpisode,
+};
+
+/// Everything recognized in a release name
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseInfo {
+    /// release group, i.e. `SubsPlease` of `[SubsPlease] ...` or `GROUP` of `...H264-GROUP`
+    pub group: Option<String>,
+    pub title: String,
+    pub season: Option<u32>,
+    pub episode: Option<Episode>,
+    /// last episode of a batch, i.e. 12 of `01-12`
+    pub episode_end: Option<Episode>,
+    /// marked as a batch without a range, i.e. `[Batch]` or `Complete`
+    #[serde(default)]
+    pub batch: bool,
+    /// release revision, i.e. 2 of `05v2`
+    pub version: Option<u32>,
+    pub year: Option<u32>,
+    /// i.e. `1080p`
+    pub resolution: Option<String>,
+    /// i.e. `BD`, `WEB-DL`
+    pub source: Option<String>,
+    /// video codec and bit depth, i.e. `HEVC`, `x265`, `10bit`
+    pub video: Vec<String>,
+    /// i.e. `AAC`, `FLAC`, `Dual-Audio`
+    pub audio: Vec<String>,
+    /// audio or subtitle languages, i.e. `ENG`, `Multi-Subs`
+    pub language: Vec<String>,
+    /// CRC32 as written, i.e. `A1B2C3D4`
+    pub crc: Option<String>,
+    pub extension: Option<String>,
+}
+
+const EXTENSIONS: &[&str] = &[
+    "mkv", "mp4", "avi", "m4v", "webm", "wmv", "flv", "mov", "ogm", "ts",
+];
+
+const SOURCES: &[&str] = &[
+    "BD",
+    "BDRIP",
+    "BDREMUX",
+    "BLURAY",
+    "BLU-RAY",
+    "BDMV",
+    "REMUX",
+    "WEB",
+    "WEB-DL",
+    "WEBDL",
+    "WEB-RIP",
+    "WEBRIP",
+    "HDTV",
+    "HDTVRIP",
+    "TV",
+    "TVRIP",
+    "DVD",
+    "DVDRIP",
+    "DVD-RIP",
+    "DVD5",
+    "DVD9",
+    "LASERDISC",
+    "VHS",
+];
+
+const VIDEO: &[&str] = &[
+    "X264", "X265", "H264", "H265", "H.264", "H.265", "HEVC", "AVC", "AV1", "VP9", "XVID", "DIVX",
+    "10BIT", "10-BIT", "10BITS", "8BIT", "8-BIT", "HI10", "HI10P", "HI444", "HDR", "HDR10", "SDR",
+];
+
+const AUDIO: &[&str] = &[
+    "AAC",
+    "FLAC",
+    "AC3",
+    "EAC3",
+    "E-AC-3",
+    "DDP",
+    "DD",
+    "DTS",
+    "DTS-HD",
+    "DTS-HDMA",
+    "TRUEHD",
+    "OPUS",
+    "MP3",
+    "VORBIS",
+    "LPCM",
+    "PCM",
+    "2.0",
+    "5.1",
+    "7.1",
+    "2CH",
+    "6CH",
+    "DUAL-AUDIO",
+    "DUALAUDIO",
+    "MULTI-AUDIO",
+    "MULTIAUDIO",
+];
+
+const LANGUAGES: &[&str] = &[
+    "ENG",
+    "ENGLISH",
+    "JPN",
+    "JAP",
+    "JAPANESE",
+    "GER",
+    "GERMAN",
+    "DEU",
+    "FRE",
+    "FRENCH",
+    "FRA",
+    "SPA",
+    "SPANISH",
+    "ITA",
+    "ITALIAN",
+    "POR",
+    "RUS",
+    "CHS",
+    "CHT",
+    "CHI",
+    "KOR",
+    "VOSTFR",
+    "MULTI",
+    "MULTISUB",
+    "MULTISUBS",
+    "MULTI-SUB",
+    "MULTI-SUBS",
+    "ESUB",
+    "ESUBS",
+    "ENG-SUB",
+    "ENGSUB",
+    "SUBBED",
+    "DUBBED",
+    "RAW",
+];
+
+/// mark batches that don't give their episode range
+const BATCH_MARKERS: &[&str] = &["BATCH", "COMPLETE"];
+
+/// keywords written as two words
+const PHRASES: &[(&str, &str, Kind)] = &[
+    ("DUAL", "AUDIO", Kind::Audio),
+    ("MULTI", "AUDIO", Kind::Audio),
+    ("MULTIPLE", "SUBTITLE", Kind::Language),
+    ("MULTIPLE", "SUBTITLES", Kind::Language),
+    ("MULTI", "SUBS", Kind::Language),
+    ("ENGLISH", "DUB", Kind::Language),
+    ("ENGLISH", "SUB", Kind::Language),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Resolution,
+    Source,
+    Video,
+    Audio,
+    Language,
+}
+
+lazy_static::lazy_static! {
+    static ref RESOLUTION: Regex = Regex::new(r"^(?i)(?:\d{3,4}[pi]|\d{3,4}x\d{3,4}|4K|2K|UHD)$").unwrap();
+    static ref AUDIO_CHANNELS: Regex = Regex::new(r"^(?i)(?:AAC|DDP|DD|EAC3|AC3|FLAC|OPUS|DTS|TRUEHD)\d\.\d$").unwrap();
+    static ref NUMBER: Regex = Regex::new(r"^(?i)#?(\d{1,4}(?:\.\d{1,3})?)(v\d{1,2})?(?:[-~](\d{1,4}(?:\.\d{1,3})?)(v\d{1,2})?)?$").unwrap();
+    static ref SEASON_EPISODE: Regex = Regex::new(r"^(?i)S(\d{1,2})E(\d{1,4}(?:\.\d{1,3})?)(v\d{1,2})?(?:-?E?(\d{1,4}))?$").unwrap();
+    static ref X_EPISODE: Regex = Regex::new(r"^(\d{1,2})x(\d{1,3})(v\d{1,2})?$").unwrap();
+    static ref EPISODE: Regex = Regex::new(r"^(?i)(?:E|EP|EP\.)(\d.*)$").unwrap();
+    static ref EPISODE_WORD: Regex = Regex::new(r"^(?i)(?:Episode|Ep\.?)$").unwrap();
+    static ref SPECIAL_WORD: Regex = Regex::new(r"^(?i)(?:SP|OVA|OAD|Special|Movie)$").unwrap();
+    static ref SPECIAL: Regex = Regex::new(r"^(?i)((?:SP|OVA|OAD|Special|Movie)\d{0,3})(v\d{1,2})?$").unwrap();
+    static ref SHORT: Regex = Regex::new(r"^(?i)S(\d{1,2})$").unwrap();
+    static ref ORDINAL: Regex = Regex::new(r"^(?i)(\d{1,2})(?:st|nd|rd|th)$").unwrap();
+    static ref VERSION: Regex = Regex::new(r"^(?i)v(\d{1,2})$").unwrap();
+}
+
+fn keyword(word: &str) -> Option<Kind> {
+    let upper = word.to_ascii_uppercase();
+    if RESOLUTION.is_match(word) {
+        Some(Kind::Resolution)
+    } else if SOURCES.contains(&&*upper) {
+        Some(Kind::Source)
+    } else if VIDEO.contains(&&*upper) {
+        Some(Kind::Video)
+    } else if AUDIO.contains(&&*upper) || AUDIO_CHANNELS.is_match(word) {
+        Some(Kind::Audio)
+    } else if LANGUAGES.contains(&&*upper) {
+        Some(Kind::Language)
+    } else {
+        None
+    }
+}
+
+/// `A1B2C3D4`
+fn is_crc(value: &str) -> bool {
+    value.len() == 8 && value.bytes().all(|x| x.is_ascii_hexdigit())
+}
+
+fn is_batch_marker(word: &str) -> bool {
+    BATCH_MARKERS.contains(&&*word.to_ascii_uppercase())
+}
+
+fn is_year(value: &str) -> Option<u32> {
+    let year: u32 = value.parse().ok()?;
+    (value.len() == 4 && (1900..=2099).contains(&year)).then_some(year)
+}
+
+/// An episode found in one or two words
+#[derive(Default, Debug, PartialEq)]
+struct EpisodeMatch {
+    season: Option<u32>,
+    episode: Option<Episode>,
+    episode_end: Option<Episode>,
+    version: Option<u32>,
+    /// words used
+    words: usize,
+}
+
+fn parse_version(value: Option<regex::Match>) -> Option<u32> {
+    value.and_then(|x| x.as_str()[1..].parse().ok())
+}
+
+/// `05`, `12.5`, `05v2`, `01-12`
+fn parse_number(word: &str) -> Option<EpisodeMatch> {
+    let captures = NUMBER.captures(word)?;
+    Some(EpisodeMatch {
+        episode: captures[1].parse().ok(),
+        episode_end: captures.get(3).and_then(|x| x.as_str().parse().ok()),
+        version: parse_version(captures.get(2).or(captures.get(4))),
+        words: 1,
+        ..Default::default()
+    })
+}
+
+/// `S01E05`, `S01E01-E12`, `1x05`, `E05`, `EP05`, `SP1`, `OVA2`, or the same split over two
+/// words like `Episode 5`, `OVA 2`
+fn parse_marked(words: &[String]) -> Option<EpisodeMatch> {
+    let word = words.first()?;
+    if let Some(captures) = SEASON_EPISODE.captures(word) {
+        return Some(EpisodeMatch {
+            season: captures[1].parse().ok(),
+            episode: captures[2].parse().ok(),
+            episode_end: captures.get(4).and_then(|x| x.as_str().parse().ok()),
+            version: parse_version(captures.get(3)),
+            words: 1,
+        });
+    }
+    if let Some(captures) = X_EPISODE.captures(word) {
+        return Some(EpisodeMatch {
+            season: captures[1].parse().ok(),
+            episode: captures[2].parse().ok(),
+            version: parse_version(captures.get(3)),
+            words: 1,
+            ..Default::default()
+        });
+    }
+    if let Some(captures) = EPISODE.captures(word) {
+        return parse_number(&captures[1]);
+    }
+    if let Some(next) = words.get(1) {
+        if EPISODE_WORD.is_match(word) {
+            return parse_number(next).map(|x| EpisodeMatch { words: 2, ..x });
+        }
+        if SPECIAL_WORD.is_match(word)
+            && !next.is_empty()
+            && next.bytes().all(|x| x.is_ascii_digit())
+        {
+            return Some(EpisodeMatch {
+                episode: format!("{word} {next}").parse().ok(),
+                words: 2,
+                ..Default::default()
+            });
+        }
+    }
+    let captures = SPECIAL.captures(word)?;
+    Some(EpisodeMatch {
+        episode: captures[1].parse().ok(),
+        version: parse_version(captures.get(2)),
+        words: 1,
+        ..Default::default()
+    })
+}
+
+/// `S2`, `Season 2`, `2nd Season` at the end of a title, returning the season and words used
+fn trailing_season(words: &[String]) -> Option<(u32, usize)> {
+    let last = words.last()?;
+    if let Some(captures) = SHORT.captures(last) {
+        return Some((captures[1].parse().ok()?, 1));
+    }
+    let [.., first, second] = words else {
+        return None;
+    };
+    if first.eq_ignore_ascii_case("Season") {
+        return Some((second.parse().ok()?, 2));
+    }
+    if second.eq_ignore_ascii_case("Season") {
+        let captures = ORDINAL.captures(first)?;
+        return Some((captures[1].parse().ok()?, 2));
+    }
+    None
+}
+
+enum Token {
+    Enclosed(String),
+    Free(String),
+}
+
+fn closing(open: char) -> Option<char> {
+    match open {
+        '[' => Some(']'),
+        '(' => Some(')'),
+        '{' => Some('}'),
+        '【' => Some('】'),
+        _ => None,
+    }
+}
+
+fn tokenize(name: &str) -> Vec<Token> {
+    let mut out = vec![];
+    let mut free = String::new();
+    let mut chars = name.char_indices();
+    while let Some((i, x)) = chars.next() {
+        let Some(close) = closing(x) else {
+            free.push(x);
+            continue;
+        };
+        let rest = &name[i + x.len_utf8()..];
+        let Some(end) = rest.find(close) else {
+            free.push(x);
+            continue;
+        };
+        if !free.trim().is_empty() {
+            out.push(Token::Free(std::mem::take(&mut free)));
+        }
+        free.clear();
+        out.push(Token::Enclosed(rest[..end].trim().to_string()));
+        // skip past the closing bracket
+        let target = i + x.len_utf8() + end + close.len_utf8();
+        while chars.clone().next().map_or(false, |(j, _)| j < target) {
+            chars.next();
+        }
+    }
+    if !free.trim().is_empty() {
+        out.push(Token::Free(free));
+    }
+    out
+}
+
+/// true for the `.` of `12.5` or `DDP5.1`, but not of `S01E05.1080p`
+fn is_decimal_point(chars: &[char], i: usize) -> bool {
+    if i == 0 || !chars[i - 1].is_ascii_digit() {
+        return false;
+    }
+    let digits = chars[i + 1..]
+        .iter()
+        .take_while(|x| x.is_ascii_digit())
+        .count();
+    (1..=3).contains(&digits)
+        && chars
+            .get(i + 1 + digits)
+            .map_or(true, |x| !x.is_alphanumeric())
+}
+
+/// Joins ranges written with spaces, `01 ~ 12`, or `01 - 12` after a ` - `, into one word.
+/// A lone spaced dash doesn't count, as in `86 - 05` it sets off the episode.
+fn join_ranges(words: Vec<String>) -> Vec<String> {
+    let number = |x: &str| -> Option<u32> {
+        x.bytes()
+            .all(|x| x.is_ascii_digit())
+            .then(|| x.parse().ok())?
+    };
+    let mut out: Vec<String> = vec![];
+    let mut i = 0;
+    while i < words.len() {
+        if let [start, separator, end, ..] = &words[i..] {
+            let after_dash = out.last().map_or(false, |x| x == "-");
+            let is_range = (separator == "~" || (separator == "-" && after_dash))
+                && number(start)
+                    .zip(number(end))
+                    .map_or(false, |(start, end)| start < end);
+            if is_range {
+                out.push(format!("{start}-{end}"));
+                i += 3;
+                continue;
+            }
+        }
+        out.push(words[i].clone());
+        i += 1;
+    }
+    out
+}
+
+/// Splits free text into words. Scene style names use `.` or `_` instead of spaces, except
+/// for decimal points.
+fn words(text: &str) -> Vec<String> {
+    let text = if text.trim().contains(' ') {
+        text.replace('_', " ")
+    } else {
+        let chars: Vec<char> = text.chars().collect();
+        chars
+            .iter()
+            .enumerate()
+            .map(|(i, x)| match x {
+                '_' => ' ',
+                '.' if !is_decimal_point(&chars, i) => ' ',
+                x => *x,
+            })
+            .collect()
+    };
+    let mut out: Vec<String> = vec![];
+    for word in text.split_whitespace() {
+        // `H 264` from `H.264` after splitting on dots
+        if let Some(last) = out.last_mut() {
+            if last.eq_ignore_ascii_case("H")
+                && (word.starts_with("264") || word.starts_with("265"))
+            {
+                last.push('.');
+                last.push_str(word);
+                continue;
+            }
+        }
+        out.push(word.to_string());
+    }
+    join_ranges(out)
+}
+
+impl ReleaseInfo {
+    fn add(&mut self, kind: Kind, word: String) {
+        match kind {
+            Kind::Resolution => {
+                self.resolution.get_or_insert(word);
+            }
+            Kind::Source => {
+                self.source.get_or_insert(word);
+            }
+            Kind::Video => self.video.push(word),
+            Kind::Audio => self.audio.push(word),
+            Kind::Language => self.language.push(word),
+        }
+    }
+
+    /// Records keywords, versions and a scene group in words after the episode, or in brackets.
+    /// Returns the words that weren't recognized.
+    fn classify(&mut self, words: &[String]) -> Vec<String> {
+        let mut unknown = vec![];
+        let mut i = 0;
+        while i < words.len() {
+            let word = &words[i];
+            if let Some(next) = words.get(i + 1) {
+                let phrase = PHRASES.iter().find(|(first, second, _)| {
+                    word.eq_ignore_ascii_case(first) && next.eq_ignore_ascii_case(second)
+                });
+                if let Some((_, _, kind)) = phrase {
+                    self.add(*kind, format!("{word} {next}"));
+                    i += 2;
+                    continue;
+                }
+            }
+            if let Some(kind) = keyword(word) {
+                self.add(kind, word.clone());
+            } else if is_batch_marker(word) {
+                self.batch = true;
+            } else if let Some(captures) = VERSION.captures(word) {
+                self.version = captures[1].parse().ok();
+            } else if let Some((left, group)) = word
+                .rsplit_once('-')
+                .filter(|(left, group)| !group.is_empty() && keyword(left).is_some())
+            {
+                // scene style `H264-GROUP`
+                self.add(keyword(left).expect("checked"), left.to_string());
+                if self.group.is_none() {
+                    self.group = Some(group.to_string());
+                }
+            } else {
+                unknown.push(word.clone());
+            }
+            i += 1;
+        }
+        unknown
+    }
+
+    fn set_episode(&mut self, found: EpisodeMatch) {
+        self.season = found.season.or(self.season);
+        self.episode = found.episode;
+        self.episode_end = found.episode_end;
+        self.version = found.version.or(self.version);
+    }
+}
+
+impl ReleaseInfo {
+    /// For pulling single episodes: fails without a title or episode, and for batches.
+    /// Everything but the title, season, episode and CRC goes in `ext`.
+    pub fn into_episode(self) -> Result<StandardEpisode> {
+        if self.title.is_empty() {
+            bail!("no title found");
+        }
+        if self.batch && self.episode_end.is_none() {
+            bail!("release is marked as a batch");
+        }
+        let episode = self.episode.ok_or_else(|| anyhow!("no episode found"))?;
+        if let Some(end) = self.episode_end {
+            bail!("episodes {episode}-{end} are a batch");
+        }
+        let checksum = match &self.crc {
+            Some(crc) => parse_checksum(crc).ok_or_else(|| anyhow!("invalid checksum '{crc}'"))?,
+            None => 0,
+        };
+        let mut ext = HashMap::new();
+        for (name, value) in [
+            ("year", self.year.map(|x| x.to_string())),
+            ("extension", self.extension),
+        ] {
+            if let Some(value) = value {
+                ext.insert(name.to_string(), value);
+            }
+        }
+        Ok(StandardEpisode {
+            title: self.title,
+            season: self.season.unwrap_or(1),
+            episode,
+            checksum,
+            resolution: self.resolution.as_deref().and_then(parse_resolution),
+            video_codec: self.video.iter().find_map(|x| normalize_video_codec(x)),
+            audio_codec: self.audio.iter().find_map(|x| normalize_audio_codec(x)),
+            source: self.source.and_then(|x| x.parse().ok()),
+            release_group: self.group,
+            version: self.version,
+            language: self.language.iter().find_map(|x| normalize_language(x)),
+            ext,
+        })
+    }
+}
+
+/// Where the title of the main words ends, and the episode found there
+fn split_title(words: &[String]) -> (usize, Option<EpisodeMatch>) {
+    // the last ` - ` followed by an episode, so titles may contain dashes
+    for (i, word) in words.iter().enumerate().rev() {
+        if word != "-" || i == 0 {
+            continue;
+        }
+        let after = &words[i + 1..];
+        let found = parse_marked(after).or_else(|| after.first().and_then(|x| parse_number(x)));
+        if let Some(found) = found {
+            return (i, Some(found));
+        }
+    }
+    // a marked episode, a keyword or a batch marker ends the title
+    for i in 1..words.len() {
+        if let Some(found) = parse_marked(&words[i..]) {
+            return (i, Some(found));
+        }
+        if keyword(&words[i]).is_some() || is_batch_marker(&words[i]) || words[i] == "-" {
+            return (i, None);
+        }
+    }
+    // `Title 05`
+    if words.len() > 1 {
+        if let Some(found) = parse_number(&words[words.len() - 1]) {
+            if is_year(&words[words.len() - 1]).is_none() {
+                return (words.len() - 1, Some(found));
+            }
+        }
+    }
+    (words.len(), None)
+}
+
+/// Parses what it can from a release name. Never fails, unrecognized parts are left out.
+pub fn parse_release(name: &str) -> ReleaseInfo {
+    let mut out = ReleaseInfo::default();
+    let mut name = name.trim();
+    if let Some((stem, extension)) = name.rsplit_once('.') {
+        if EXTENSIONS.contains(&&*extension.to_ascii_lowercase()) {
+            out.extension = Some(extension.to_string());
+            name = stem;
+        }
+    }
+
+    let mut tokens = tokenize(name).into_iter().peekable();
+    if let Some(Token::Enclosed(group)) = tokens.peek() {
+        if !group.is_empty() {
+            out.group = Some(group.clone());
+        }
+        tokens.next();
+    }
+
+    // free text up to the first bracket after it, except a year as in `Title (2023) - 04`
+    let mut main = vec![];
+    let mut main_done = false;
+    let mut enclosed = vec![];
+    let mut trailing = vec![];
+    for token in tokens {
+        match token {
+            Token::Free(text) if !main_done => main.extend(words(&text)),
+            Token::Free(text) => trailing.extend(words(&text)),
+            Token::Enclosed(text) => {
+                match is_year(&text) {
+                    Some(year) if !main.is_empty() && !main_done => {
+                        out.year.get_or_insert(year);
+                        continue;
+                    }
+                    _ => main_done = !main.is_empty(),
+                }
+                enclosed.push(text);
+            }
+        }
+    }
+
+    let (end, found) = split_title(&main);
+    let mut title = main[..end].to_vec();
+    let mut rest = vec![];
+    if let Some(found) = found {
+        let used = found.words;
+        out.set_episode(found);
+        // skip a ` - ` before the episode
+        let start = if main.get(end).map(String::as_str) == Some("-") {
+            end + 1
+        } else {
+            end
+        };
+        rest.extend_from_slice(&main[(start + used).min(main.len())..]);
+    } else {
+        rest.extend_from_slice(&main[end..]);
+    }
+    rest.extend(trailing);
+    out.classify(&rest);
+
+    for text in enclosed {
+        if is_crc(&text) {
+            out.crc.get_or_insert(text);
+            continue;
+        }
+        if let Some(year) = is_year(&text) {
+            out.year.get_or_insert(year);
+            continue;
+        }
+        let words = join_ranges(
+            text.split(|x: char| x.is_whitespace() || x == ',' || x == '_')
+                .filter(|x| !x.is_empty())
+                .map(str::to_string)
+                .collect(),
+        );
+        if out.episode.is_none() {
+            // `[Group] Title [05][1080p]`
+            let found = parse_marked(&words).or_else(|| {
+                (words.len() == 1)
+                    .then(|| parse_number(&words[0]))
+                    .flatten()
+            });
+            if let Some(found) = found {
+                out.set_episode(found);
+                continue;
+            }
+        }
+        out.classify(&words);
+    }
+
+    if let Some((season, used)) = trailing_season(&title) {
+        out.season.get_or_insert(season);
+        title.truncate(title.len() - used);
+    }
+    while title
+        .last()
+        .map_or(false, |x| x == "-" || x == ":" || x == "|")
+    {
+        title.pop();
+    }
+    out.title = title.join(" ");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|x| x.to_string()).collect()
+    }
+
+    /// (name, group, title, season, episode)
+    type Case = (
+        &'static str,
+        Option<&'static str>,
+        &'static str,
+        Option<u32>,
+        Option<&'static str>,
+    );
+
+    const CORPUS: &[Case] = &[
+        (
+            "[SubsPlease] Spy x Family - 05 (1080p) [A1B2C3D4].mkv",
+            Some("SubsPlease"),
+            "Spy x Family",
+            None,
+            Some("5"),
+        ),
+        (
+            "[SubsPlease] Mushoku Tensei S2 - 05v2 (1080p) [A1B2C3D4].mkv",
+            Some("SubsPlease"),
+            "Mushoku Tensei",
+            Some(2),
+            Some("5"),
+        ),
+        (
+            "[Erai-raws] Spy x Family - 05 [1080p][Multiple Subtitle][ABCD1234].mkv",
+            Some("Erai-raws"),
+            "Spy x Family",
+            None,
+            Some("5"),
+        ),
+        (
+            "Spy.x.Family.S01E05.1080p.WEB.H264-GROUP.mkv",
+            Some("GROUP"),
+            "Spy x Family",
+            Some(1),
+            Some("5"),
+        ),
+        (
+            "[Judas] Oshi no Ko - S02E03.mkv",
+            Some("Judas"),
+            "Oshi no Ko",
+            Some(2),
+            Some("3"),
+        ),
+        (
+            "[ASW] Tonikaku Kawaii - 12.5 [1080p HEVC x265 10Bit][AAC]",
+            Some("ASW"),
+            "Tonikaku Kawaii",
+            None,
+            Some("12.5"),
+        ),
+        (
+            "[Group] Re:Zero - Starting Life in Another World - 05 [720p].mkv",
+            Some("Group"),
+            "Re:Zero - Starting Life in Another World",
+            None,
+            Some("5"),
+        ),
+        (
+            "[Group] Title - 05 - The Episode Name [1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("5"),
+        ),
+        (
+            "[Group] One Piece - 1080 [1080p].mkv",
+            Some("Group"),
+            "One Piece",
+            None,
+            Some("1080"),
+        ),
+        (
+            "[Group] Oshi no Ko 2nd Season - 03 [1080p].mkv",
+            Some("Group"),
+            "Oshi no Ko",
+            Some(2),
+            Some("3"),
+        ),
+        (
+            "[Group] Title Season 3 - 01 [1080p].mkv",
+            Some("Group"),
+            "Title",
+            Some(3),
+            Some("1"),
+        ),
+        (
+            "[Group] Title - SP1 [1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("SP1"),
+        ),
+        (
+            "[Group] Title OVA 2 [BD 1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("OVA2"),
+        ),
+        (
+            "[Group] Title - Movie [BD 1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("Movie"),
+        ),
+        (
+            "[Group] Title Episode 7 [720p].mp4",
+            Some("Group"),
+            "Title",
+            None,
+            Some("7"),
+        ),
+        (
+            "[Group] Title EP07 (1080p).mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("7"),
+        ),
+        (
+            "[Group] Title 07 [1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("7"),
+        ),
+        (
+            "[Group] Title [07][1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("7"),
+        ),
+        (
+            "[Group] Title (2023) - 04 [1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("4"),
+        ),
+        (
+            "Title_-_06_[720p]_[Group].mkv",
+            None,
+            "Title",
+            None,
+            Some("6"),
+        ),
+        (
+            "Title 1x06 720p HDTV x264-GRP.mkv",
+            Some("GRP"),
+            "Title",
+            Some(1),
+            Some("6"),
+        ),
+        (
+            "【Group】 Title - 09 [1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("9"),
+        ),
+        (
+            "[Group] Kaguya-sama wa Kokurasetai - 03 [1080p].mkv",
+            Some("Group"),
+            "Kaguya-sama wa Kokurasetai",
+            None,
+            Some("3"),
+        ),
+        (
+            "[Group] Title [BD 1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            None,
+        ),
+        (
+            "[Group] 86 - 05 [1080p].mkv",
+            Some("Group"),
+            "86",
+            None,
+            Some("5"),
+        ),
+        (
+            "[Erai-raws] Boku no Hero Academia 6th Season - 01 [1080p][Multiple Subtitle][ENG].mkv",
+            Some("Erai-raws"),
+            "Boku no Hero Academia",
+            Some(6),
+            Some("1"),
+        ),
+        (
+            "[EMBER] Vinland Saga S2 - 01 [1080p] [HEVC WEBRip DDP]",
+            Some("EMBER"),
+            "Vinland Saga",
+            Some(2),
+            Some("1"),
+        ),
+        (
+            "[Anime Time] Naruto Shippuden - 001 [1080p][HEVC 10bit x265][AAC][Multi Sub]",
+            Some("Anime Time"),
+            "Naruto Shippuden",
+            None,
+            Some("1"),
+        ),
+        (
+            "[SubsPlease] Dr. Stone - New World - 05 (1080p) [D0B3C4AB].mkv",
+            Some("SubsPlease"),
+            "Dr. Stone - New World",
+            None,
+            Some("5"),
+        ),
+        (
+            "[HorribleSubs] Boruto - Naruto Next Generations - 100 [720p].mkv",
+            Some("HorribleSubs"),
+            "Boruto - Naruto Next Generations",
+            None,
+            Some("100"),
+        ),
+        (
+            "Frieren.Beyond.Journeys.End.S01E12.Ein.alter.Freund.1080p.CR.WEB-DL.AAC2.0.H.264-VARYG.mkv",
+            Some("VARYG"),
+            "Frieren Beyond Journeys End",
+            Some(1),
+            Some("12"),
+        ),
+        (
+            "[Yameii] The Apothecary Diaries - S01E05 [English Dub] [CR WEB-DL 1080p] [1A2B3C4D]",
+            Some("Yameii"),
+            "The Apothecary Diaries",
+            Some(1),
+            Some("5"),
+        ),
+        (
+            "[DKB] Jujutsu Kaisen - S02E18 [1080p][HEVC x265 10bit][Multi-Subs][weekly]",
+            Some("DKB"),
+            "Jujutsu Kaisen",
+            Some(2),
+            Some("18"),
+        ),
+        (
+            "[Group] Title - 13 END [1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("13"),
+        ),
+        (
+            "[Group] Title - 05 (BD 1920x1080 x264 FLAC).mkv",
+            Some("Group"),
+            "Title",
+            None,
+            Some("5"),
+        ),
+        (
+            "[Group] Title - 01 ~ 12 [1080p]",
+            Some("Group"),
+            "Title",
+            None,
+            Some("1"),
+        ),
+        (
+            "[Group] Title - 01 - 12 (BD 1080p)",
+            Some("Group"),
+            "Title",
+            None,
+            Some("1"),
+        ),
+        (
+            "[Group] Title [01 ~ 12][1080p]",
+            Some("Group"),
+            "Title",
+            None,
+            Some("1"),
+        ),
+        (
+            "[Group] Title [Batch][1080p].mkv",
+            Some("Group"),
+            "Title",
+            None,
+            None,
+        ),
+        (
+            "[Group] Title Complete Series [BD 1080p]",
+            Some("Group"),
+            "Title",
+            None,
+            None,
+        ),
+    ];
+
+    #[test]
+    fn test_corpus() {
+        for (name, group, title, season, episode) in CORPUS {
+            let parsed = parse_release(name);
+            assert_eq!(parsed.group.as_deref(), *group, "group of {name}");
+            assert_eq!(parsed.title, *title, "title of {name}");
+            assert_eq!(parsed.season, *season, "season of {name}");
+            assert_eq!(
+                parsed.episode.map(|x| x.to_string()).as_deref(),
+                *episode,
+                "episode of {name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_metadata() {
+        let parsed = parse_release("[SubsPlease] Mushoku Tensei S2 - 05v2 (1080p) [A1B2C3D4].mkv");
+        assert_eq!(parsed.version, Some(2));
+        assert_eq!(parsed.resolution.as_deref(), Some("1080p"));
+        assert_eq!(parsed.crc.as_deref(), Some("A1B2C3D4"));
+        assert_eq!(parsed.extension.as_deref(), Some("mkv"));
+
+        let parsed = parse_release(
+            "[Group] Title - 03 [BD 1080p HEVC x265 10bit][FLAC 2.0][Dual-Audio][ENG][DEADBEEF].mkv",
+        );
+        assert_eq!(parsed.source.as_deref(), Some("BD"));
+        assert_eq!(parsed.video, strings(&["HEVC", "x265", "10bit"]));
+        assert_eq!(parsed.audio, strings(&["FLAC", "2.0", "Dual-Audio"]));
+        assert_eq!(parsed.language, strings(&["ENG"]));
+        assert_eq!(parsed.crc.as_deref(), Some("DEADBEEF"));
+
+        let parsed = parse_release("Spy.x.Family.S01E05.1080p.WEB-DL.DDP5.1.H.264-GROUP.mkv");
+        assert_eq!(parsed.source.as_deref(), Some("WEB-DL"));
+        assert_eq!(parsed.audio, strings(&["DDP5.1"]));
+        assert_eq!(parsed.video, strings(&["H.264"]));
+        assert_eq!(parsed.group.as_deref(), Some("GROUP"));
+
+        let parsed = parse_release("[Group] Title - 01 [Multiple Subtitle] [Dual Audio]");
+        assert_eq!(parsed.language, strings(&["Multiple Subtitle"]));
+        assert_eq!(parsed.audio, strings(&["Dual Audio"]));
+        assert_eq!(parsed.extension, None);
+
+        let parsed = parse_release("[Group] Title (2023) - 04 [1080p].mkv");
+        assert_eq!(parsed.year, Some(2023));
+
+        let parsed = parse_release("[Group] Title - 05 (BD 1920x1080 x264 FLAC).mkv");
+        assert_eq!(parsed.resolution.as_deref(), Some("1920x1080"));
+
+        let parsed = parse_release(
+            "[Yameii] The Apothecary Diaries - S01E05 [English Dub] [CR WEB-DL 1080p] [1A2B3C4D]",
+        );
+        assert_eq!(parsed.language, strings(&["English Dub"]));
+        assert_eq!(parsed.source.as_deref(), Some("WEB-DL"));
+        assert_eq!(parsed.crc.as_deref(), Some("1A2B3C4D"));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let parsed = parse_release("[Group] Title - 01-12 (BD 1080p) [Batch]");
+        assert_eq!(parsed.episode, Some(Episode::Standard(1)));
+        assert_eq!(parsed.episode_end, Some(Episode::Standard(12)));
+
+        let parsed = parse_release("Title.S02E01-E12.1080p.BluRay.x265-GRP");
+        assert_eq!(parsed.season, Some(2));
+        assert_eq!(parsed.episode, Some(Episode::Standard(1)));
+        assert_eq!(parsed.episode_end, Some(Episode::Standard(12)));
+        assert_eq!(parsed.source.as_deref(), Some("BluRay"));
+
+        for name in [
+            "[Group] Title - 01 ~ 12 [1080p]",
+            "[Group] Title - 01 - 12 (BD 1080p)",
+            "[Group] Title 01 ~ 12 [1080p]",
+        ] {
+            let parsed = parse_release(name);
+            assert_eq!(parsed.title, "Title", "title of {name}");
+            assert_eq!(parsed.episode, Some(Episode::Standard(1)), "{name}");
+            assert_eq!(parsed.episode_end, Some(Episode::Standard(12)), "{name}");
+            let error = parsed.into_episode().unwrap_err();
+            assert_eq!(error.to_string(), "episodes 1-12 are a batch");
+        }
+        // a dash setting off the episode isn't a range
+        let parsed = parse_release("[Group] Title 2 - 05 [1080p]");
+        assert_eq!(parsed.episode, Some(Episode::Standard(5)));
+        assert_eq!(parsed.episode_end, None);
+
+        for name in [
+            "[Group] Title [Batch][1080p].mkv",
+            "[Group] Title (Complete) [1080p]",
+            "[Group] Title Complete Series [BD 1080p]",
+        ] {
+            let parsed = parse_release(name);
+            assert!(parsed.batch, "{name}");
+            let error = parsed.into_episode().unwrap_err();
+            assert_eq!(error.to_string(), "release is marked as a batch", "{name}");
+        }
+    }
+
+    #[test]
+    fn test_unparseable() {
+        let parsed = parse_release("");
+        assert_eq!(parsed, ReleaseInfo::default());
+        let parsed = parse_release("[Group]");
+        assert_eq!(parsed.group.as_deref(), Some("Group"));
+        assert_eq!(parsed.title, "");
+        let parsed = parse_release("Just A Title");
+        assert_eq!(parsed.title, "Just A Title");
+        assert_eq!(parsed.episode, None);
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{RegexWrapper, StandardEpisode};
+
+fn default_strip() -> bool {
+    true
+}
+
+/// Detects the season from the parsed title, for releases like "Oshi no Ko 2nd Season",
+/// "Title II" or "Title: Kanketsu-hen" that don't have a season the profile can capture
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SeasonRule {
+    /// matched against the parsed title
+    pub pattern: RegexWrapper,
+    /// season of matching titles. If unset, `pattern` must capture `season`, as a number or
+    /// roman numeral
+    #[serde(default)]
+    pub season: Option<u32>,
+    /// if true, the match is removed from the title, so it is the series' title again
+    #[serde(default = "default_strip")]
+    pub strip: bool,
+}
+
+/// `I` through `XXXIX`, enough for seasons
+fn parse_roman(value: &str) -> Option<u32> {
+    let mut out = 0;
+    let mut previous = 0;
+    for x in value.chars().rev() {
+        let digit = match x.to_ascii_uppercase() {
+            'I' => 1,
+            'V' => 5,
+            'X' => 10,
+            _ => return None,
+        };
+        if digit < previous {
+            out -= digit;
+        } else {
+            out += digit;
+            previous = digit;
+        }
+    }
+    (out > 0).then_some(out)
+}
+
+impl SeasonRule {
+    /// Some(season) if the rule applies, stripping the match from `title` if configured
+    fn apply(&self, title: &mut String) -> Result<Option<u32>> {
+        let Some(captures) = self.pattern.captures(title) else {
+            return Ok(None);
+        };
+        let season = match (self.season, captures.name("season")) {
+            (Some(season), _) => season,
+            (None, Some(value)) => {
+                let value = value.as_str();
+                value
+                    .parse()
+                    .ok()
+                    .or_else(|| parse_roman(value))
+                    .ok_or_else(|| anyhow!("invalid season '{value}'"))?
+            }
+            (None, None) => {
+                return Err(anyhow!(
+                    "season rule '{}' sets no season and captures none",
+                    self.pattern.as_str()
+                ))
+            }
+        };
+        if self.strip {
+            let matched = captures.get(0).expect("whole match").range();
+            title.replace_range(matched, "");
+            let trimmed = title
+                .trim_matches(|x: char| x.is_whitespace() || matches!(x, ':' | '-' | '_'))
+                .to_string();
+            *title = trimmed;
+        }
+        Ok(Some(season))
+    }
+}
+
+/// Applies the first matching rule, if any
+pub fn apply_season_rules(rules: &[SeasonRule], parsed: &mut StandardEpisode) -> Result<()> {
+    for rule in rules {
+        if let Some(season) = rule.apply(&mut parsed.title)? {
+            parsed.season = season;
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn rule(pattern: &str, season: Option<u32>) -> SeasonRule {
+        SeasonRule {
+            pattern: RegexWrapper(Regex::new(pattern).unwrap()),
+            season,
+            strip: true,
+        }
+    }
+
+    fn applied(rules: &[SeasonRule], title: &str) -> (String, u32) {
+        let mut parsed = StandardEpisode {
+            title: title.to_string(),
+            season: 1,
+            ..Default::default()
+        };
+        apply_season_rules(rules, &mut parsed).unwrap();
+        (parsed.title, parsed.season)
+    }
+
+    #[test]
+    fn test_season_rules() {
+        let rules = [
+            rule(r"(?i)(?P<season>\d+)(?:st|nd|rd|th) Season$", None),
+            rule(r" (?P<season>[IVX]+)$", None),
+            rule(r"(?i): Kanketsu-hen$", Some(3)),
+        ];
+        assert_eq!(
+            applied(&rules, "Oshi no Ko 2nd Season"),
+            ("Oshi no Ko".to_string(), 2)
+        );
+        assert_eq!(applied(&rules, "Title II"), ("Title".to_string(), 2));
+        assert_eq!(applied(&rules, "Title IV"), ("Title".to_string(), 4));
+        assert_eq!(
+            applied(&rules, "Title: Kanketsu-hen"),
+            ("Title".to_string(), 3)
+        );
+        assert_eq!(applied(&rules, "Title"), ("Title".to_string(), 1));
+    }
+
+    #[test]
+    fn test_season_rule_without_season() {
+        let mut parsed = StandardEpisode {
+            title: "Title Final".to_string(),
+            ..Default::default()
+        };
+        assert!(apply_season_rules(&[rule("Final", None)], &mut parsed).is_err());
+    }
+
+    #[test]
+    fn test_parse_roman() {
+        assert_eq!(parse_roman("I"), Some(1));
+        assert_eq!(parse_roman("IX"), Some(9));
+        assert_eq!(parse_roman("XIV"), Some(14));
+        assert_eq!(parse_roman("Q"), None);
+    }
+}
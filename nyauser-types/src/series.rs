@@ -1,8 +1,11 @@
 use std::collections::BTreeMap;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use super::{Episode, ParsedSearchResult, PullState};
+use super::{
+    DownloadProgress, Episode, EpisodeRule, ParsedSearchResult, PullEntry, PullState, SeasonRule,
+};
 
 fn default_relocate_season() -> bool {
     true
@@ -11,6 +14,9 @@ fn default_relocate_season() -> bool {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Series {
     pub name: String,
+    /// other titles groups release this series under, searched for and accepted as well as `name`
+    #[serde(default)]
+    pub aliases: Vec<String>,
     /// profile name to search for this series
     pub profile: String,
     /// override for `SearchConfig::max_days_old`
@@ -20,6 +26,59 @@ pub struct Series {
     /// if true, `Season X` is appended to the relocate path
     #[serde(default = "default_relocate_season")]
     pub relocate_season: bool,
+    /// maximum torrents in the sink at once for this series, further grabs are queued
+    #[serde(default)]
+    pub max_active_downloads: Option<usize>,
+    /// what searches do with episodes a library rescan found missing
+    #[serde(default)]
+    pub missing_policy: MissingPolicy,
+    /// detects the season from the parsed title, the first matching rule applies
+    #[serde(default)]
+    pub season_rules: Vec<SeasonRule>,
+    /// maps parsed numbering onto this series' seasons, the first matching rule applies
+    #[serde(default)]
+    pub episode_rules: Vec<EpisodeRule>,
+    /// if true, specials, OVAs, movies and decimal episodes go in season 0, as Plex and Jellyfin expect
+    #[serde(default)]
+    pub specials_season_zero: bool,
+}
+
+impl Series {
+    /// `name`, then each alias
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.aliases.iter().map(String::as_str))
+    }
+
+    /// true if a parsed release title is this series, ignoring case, punctuation and width
+    pub fn matches_title(&self, title: &str) -> bool {
+        let title = normalize_title(title);
+        self.names().any(|x| normalize_title(x) == title)
+    }
+}
+
+/// Folds a title for comparison: full-width forms become ASCII, `×` becomes `x`,
+/// and everything but lowercased letters and digits is dropped
+pub fn normalize_title(title: &str) -> String {
+    title
+        .chars()
+        .map(|x| match x {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(x as u32 - 0xFEE0).unwrap_or(x),
+            '×' => 'x',
+            x => x,
+        })
+        .filter(|x| x.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingPolicy {
+    /// missing episodes were likely deleted on purpose, never grab them again
+    #[default]
+    Keep,
+    /// grab missing episodes again
+    Regrab,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -29,6 +88,31 @@ pub struct SeriesStatus {
     pub seasons: BTreeMap<u32, SeasonStatus>,
 }
 
+impl SeriesStatus {
+    pub fn new(series: Series, pulls: impl IntoIterator<Item = PullEntry>) -> Self {
+        let mut seasons: BTreeMap<u32, SeasonStatus> = BTreeMap::new();
+        for pull in pulls {
+            seasons
+                .entry(pull.result.parsed.season)
+                .or_default()
+                .episodes
+                .insert(
+                    pull.result.parsed.episode.clone(),
+                    EpisodeStatus {
+                        state: pull.state,
+                        reason: pull.reason().map(str::to_string),
+                        since: pull.last_transition().map(|x| x.at),
+                        progress: pull
+                            .progress
+                            .filter(|_| pull.state == PullState::Downloading),
+                        source: pull.result,
+                    },
+                );
+        }
+        Self { seasons, series }
+    }
+}
+
 #[derive(Default, Serialize, Deserialize, Clone)]
 pub struct SeasonStatus {
     pub episodes: BTreeMap<Episode, EpisodeStatus>,
@@ -37,5 +121,47 @@ pub struct SeasonStatus {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct EpisodeStatus {
     pub state: PullState,
+    /// why the episode is in its current state, i.e. why it failed
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// when the episode entered its current state
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    /// sink progress while downloading
+    #[serde(default)]
+    pub progress: Option<DownloadProgress>,
     pub source: ParsedSearchResult,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_title() {
+        assert_eq!(normalize_title("Spy x Family"), "spyxfamily");
+        assert_eq!(normalize_title("SPY×FAMILY"), "spyxfamily");
+        assert_eq!(normalize_title("ＳＰＹ×ＦＡＭＩＬＹ"), "spyxfamily");
+        assert_eq!(
+            normalize_title("Kaguya-sama: Love is War"),
+            "kaguyasamaloveiswar"
+        );
+        assert_eq!(normalize_title("Oshi no Ko"), "oshinoko");
+    }
+
+    #[test]
+    fn test_matches_title() {
+        let series: Series = serde_json::from_value(serde_json::json!({
+            "name": "Tonikaku Kawaii",
+            "aliases": ["Fly Me to the Moon"],
+            "profile": "subsplease",
+            "max_days_old": null,
+            "relocate": null,
+        }))
+        .unwrap();
+        assert!(series.matches_title("TONIKAKU KAWAII"));
+        assert!(series.matches_title("Fly Me to the Moon!"));
+        assert!(!series.matches_title("Tonikaku Kawaii Specials"));
+        assert!(!series.matches_title("Kawaii"));
+    }
+}
@@ -17,12 +17,12 @@ impl<S: Send + Sync> FromRequestParts<S> for Auth {
     async fn from_request_parts(req: &mut Parts, state: &S) -> ApiResult<Self> {
         let AuthBasic((username, Some(password))) = AuthBasic::from_request_parts(req, state)
             .await
-            .map_err(|e| ApiError::Arbitrary(e.into_response()))?
+            .map_err(|(_, message)| ApiError::Unauthorized(message.to_string()))?
         else {
-            return Err(ApiError::Unauthorized(format!("missing password")));
+            return Err(ApiError::Unauthorized("missing password".to_string()));
         };
         if username != CONFIG.rpc_username || password != CONFIG.rpc_password {
-            return Err(ApiError::Unauthorized(format!("invalid credentials")));
+            return Err(ApiError::Unauthorized("invalid credentials".to_string()));
         }
         Ok(Self { _p: () })
     }
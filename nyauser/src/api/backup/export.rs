@@ -0,0 +1,8 @@
+use axum::extract::State;
+
+use super::*;
+
+pub(super) async fn export(_: Auth, State(state): State<AppState>) -> ApiResult<Json<Backup>> {
+    let backup = tokio::task::spawn_blocking(move || state.database.export()).await?;
+    Ok(Json(backup.map_err(ApiError::Other)?))
+}
@@ -0,0 +1,10 @@
+use super::*;
+
+mod export;
+mod restore;
+
+pub fn route() -> Router<AppState> {
+    Router::new()
+        .route("/", routing::get(export::export))
+        .route("/", routing::post(restore::restore))
+}
@@ -0,0 +1,14 @@
+use axum::extract::{Query, State};
+
+use super::*;
+
+pub(super) async fn restore(
+    _: Auth,
+    State(state): State<AppState>,
+    Query(query): Query<RestoreQuery>,
+    Json(backup): Json<Backup>,
+) -> ApiResult<Json<RestoreReport>> {
+    let report =
+        tokio::task::spawn_blocking(move || state.database.restore(backup, query.mode)).await?;
+    Ok(Json(report.map_err(ApiError::Other)?))
+}
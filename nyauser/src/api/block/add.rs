@@ -0,0 +1,29 @@
+use axum::extract::State;
+
+use super::*;
+
+pub(super) async fn add(
+    _: Auth,
+    State(state): State<AppState>,
+    Json(body): Json<BlockRequest>,
+) -> ApiResult<Json<BlockEntry>> {
+    let block = BlockEntry::new(body.rule, body.reason);
+    state.database.save_block(&block).map_err(ApiError::Other)?;
+    // an episode that isn't downloading or done is marked as ignored straight away
+    if let BlockRule::Episode(key) = &block.rule {
+        if let Some(mut pull) = state
+            .database
+            .get_pull_entry(key)
+            .map_err(ApiError::Other)?
+        {
+            if pull.torrent_id.is_none()
+                && !pull.state.is_complete()
+                && pull.state != PullState::Ignored
+            {
+                pull.transition(PullState::Ignored, block.reason.clone());
+                state.database.save_pull(&pull).map_err(ApiError::Other)?;
+            }
+        }
+    }
+    Ok(Json(block))
+}
@@ -0,0 +1,29 @@
+use axum::extract::{Path, State};
+
+use super::*;
+
+pub(super) async fn delete(
+    _: Auth,
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> ApiResult<()> {
+    let block = state
+        .database
+        .get_block(&id)
+        .map_err(ApiError::Other)?
+        .ok_or(ApiError::NotFound)?;
+    state.database.delete_block(&id).map_err(ApiError::Other)?;
+    // forget the ignored episode so it can be grabbed again
+    if let BlockRule::Episode(key) = &block.rule {
+        if let Some(pull) = state
+            .database
+            .get_pull_entry(key)
+            .map_err(ApiError::Other)?
+        {
+            if pull.state == PullState::Ignored {
+                state.database.delete_pull(pull).map_err(ApiError::Other)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,14 @@
+use axum::extract::State;
+
+use super::*;
+
+pub(super) async fn list(
+    _: Auth,
+    State(state): State<AppState>,
+) -> ApiResult<Json<Vec<BlockEntry>>> {
+    state
+        .database
+        .list_block()
+        .map_err(ApiError::Other)
+        .map(Json)
+}
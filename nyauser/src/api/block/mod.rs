@@ -0,0 +1,12 @@
+use super::*;
+
+mod add;
+mod delete;
+mod list;
+
+pub fn route() -> Router<AppState> {
+    Router::new()
+        .route("/", routing::get(list::list))
+        .route("/", routing::post(add::add))
+        .route("/:id", routing::delete(delete::delete))
+}
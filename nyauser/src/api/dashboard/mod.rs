@@ -0,0 +1,10 @@
+use axum::response::Html;
+
+use super::*;
+
+/// single page dashboard, compiled into the binary so deployments stay a single file
+const INDEX: &str = include_str!("index.html");
+
+pub(super) async fn index(_: Auth) -> Html<&'static str> {
+    Html(INDEX)
+}
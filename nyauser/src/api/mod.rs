@@ -14,6 +14,7 @@ use tokio::sync::Notify;
 
 use crate::api::auth::Auth;
 use crate::db::Database;
+use crate::search::DynSearcher;
 use serde::{Deserialize, Serialize};
 
 use crate::config::CONFIG;
@@ -24,6 +25,9 @@ use nyauser_types::*;
 
 mod auth;
 
+mod backup;
+mod block;
+mod dashboard;
 mod logger;
 mod proc;
 mod profile;
@@ -65,9 +69,12 @@ impl IntoResponse for ApiError {
             ApiError::Found(destination) => {
                 Redirect::temporary(destination.as_str()).into_response()
             }
-            ApiError::Unauthorized(message) => {
-                (StatusCode::UNAUTHORIZED, Json(ErrorBody { message })).into_response()
-            }
+            ApiError::Unauthorized(message) => (
+                StatusCode::UNAUTHORIZED,
+                [(http::header::WWW_AUTHENTICATE, "Basic realm=\"nyauser\"")],
+                Json(ErrorBody { message }),
+            )
+                .into_response(),
             ApiError::BadRequest(message) => {
                 (StatusCode::BAD_REQUEST, Json(ErrorBody { message })).into_response()
             }
@@ -99,6 +106,7 @@ pub struct AppState {
     pub database: Arc<Database>,
     pub scan: Arc<Notify>,
     pub search: Arc<Notify>,
+    pub searcher: Arc<DynSearcher>,
 }
 
 async fn health() {}
@@ -108,22 +116,30 @@ fn route(state: AppState) -> Router {
         .nest("/series", series::route())
         .nest("/profile", profile::route())
         .nest("/pull", pull::route())
+        .nest("/block", block::route())
         .nest("/proc", proc::route())
+        .nest("/backup", backup::route())
         .route("/health", routing::get(health))
         .with_state(state);
 
-    let nest = if let Some(base_path) = &CONFIG.rpc_base_path {
+    let base = if let Some(base_path) = &CONFIG.rpc_base_path {
         let mut base = base_path.clone();
         if !base.ends_with('/') {
             base.push('/');
         }
-        base.push_str("api/v1");
         base
     } else {
-        "/api/v1".to_string()
+        "/".to_string()
     };
+    let nest = format!("{base}api/v1");
 
-    Router::new().nest(&nest, api).layer(LoggerLayer)
+    let mut router = Router::new()
+        .nest(&nest, api)
+        .route(&base, routing::get(dashboard::index));
+    if base != "/" {
+        router = router.route(base.trim_end_matches('/'), routing::get(dashboard::index));
+    }
+    router.layer(LoggerLayer)
 }
 
 pub fn spawn_api_server(state: AppState) {
@@ -1,6 +1,6 @@
-use axum::extract::State;
+use axum::extract::{Query, State};
 
-use crate::search::wipe_nonexistant;
+use crate::search::{fix_permissions, rescan_library, wipe_nonexistant};
 
 use super::*;
 
@@ -9,6 +9,10 @@ pub fn route() -> Router<AppState> {
         .route("/scan", routing::get(scan))
         .route("/search", routing::get(search))
         .route("/wipe_deleted", routing::get(wipe_deleted))
+        .route("/import", routing::post(import))
+        .route("/rescan", routing::post(rescan))
+        .route("/fix_permissions", routing::post(fix_permissions_all))
+        .route("/fsck", routing::post(fsck))
 }
 
 async fn scan(_auth: Auth, State(state): State<AppState>) {
@@ -22,3 +26,40 @@ async fn search(_auth: Auth, State(state): State<AppState>) {
 async fn wipe_deleted(_auth: Auth, State(state): State<AppState>) -> ApiResult<()> {
     wipe_nonexistant(&state.database).map_err(ApiError::Other)
 }
+
+async fn import(
+    _auth: Auth,
+    State(state): State<AppState>,
+    Json(request): Json<LibraryImportRequest>,
+) -> ApiResult<Json<LibraryImportReport>> {
+    // walks the relocate directories, which can take a while on large libraries
+    let report =
+        tokio::task::spawn_blocking(move || state.searcher.import_library(&request)).await?;
+    Ok(Json(report.map_err(ApiError::Other)?))
+}
+
+async fn rescan(
+    _auth: Auth,
+    State(state): State<AppState>,
+) -> ApiResult<Json<LibraryRescanReport>> {
+    let report = tokio::task::spawn_blocking(move || rescan_library(&state.database)).await?;
+    Ok(Json(report.map_err(ApiError::Other)?))
+}
+
+/// returns how many files and directories were updated
+async fn fix_permissions_all(_auth: Auth, State(state): State<AppState>) -> ApiResult<Json<usize>> {
+    let count = tokio::task::spawn_blocking(move || {
+        fix_permissions(&state.database, &CONFIG.search.permissions)
+    })
+    .await?;
+    Ok(Json(count.map_err(ApiError::Other)?))
+}
+
+async fn fsck(
+    _auth: Auth,
+    State(state): State<AppState>,
+    Query(query): Query<FsckQuery>,
+) -> ApiResult<Json<Vec<FsckFinding>>> {
+    let findings = tokio::task::spawn_blocking(move || state.database.fsck(query.repair)).await?;
+    Ok(Json(findings.map_err(ApiError::Other)?))
+}
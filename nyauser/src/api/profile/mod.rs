@@ -3,11 +3,13 @@ use super::*;
 mod delete;
 mod get;
 mod list;
+mod test;
 mod update;
 
 pub fn route() -> Router<AppState> {
     Router::new()
         .route("/", routing::get(list::list))
+        .route("/test", routing::post(test::test))
         .route("/:name", routing::get(get::get))
         .route("/:name", routing::post(update::update))
         .route("/:name", routing::delete(delete::delete))
@@ -0,0 +1,64 @@
+use axum::extract::State;
+
+use crate::source::Source;
+
+use super::*;
+
+pub(super) async fn test(
+    _: Auth,
+    State(state): State<AppState>,
+    Json(body): Json<ProfileTestRequest>,
+) -> ApiResult<Json<Vec<ProfileTestResult>>> {
+    let profile = match body.profile {
+        ProfileRef::Named(name) => state
+            .database
+            .get_profile(&name)
+            .map_err(ApiError::Other)?
+            .ok_or(ApiError::NotFound)?,
+        ProfileRef::Inline(profile) => *profile,
+    };
+    let series = match &body.series {
+        Some(name) => Some(
+            state
+                .database
+                .get_series(name)
+                .map_err(ApiError::Other)?
+                .ok_or(ApiError::NotFound)?,
+        ),
+        None => None,
+    };
+    let mut titles = body.titles;
+    if let Some(query) = &body.query {
+        let results = state
+            .searcher
+            .source()
+            .search(query)
+            .await
+            .map_err(ApiError::Other)?;
+        titles.extend(results.into_iter().map(|result| result.title));
+    }
+    Ok(Json(
+        titles
+            .into_iter()
+            .map(|title| {
+                // the searcher parses with the series' rules applied
+                let parsed = match &series {
+                    Some(series) => profile.parse_for_series(&title, series),
+                    None => profile.try_parse_name(&title),
+                };
+                match parsed {
+                    Ok(parsed) => ProfileTestResult {
+                        title,
+                        parsed: Some(parsed),
+                        error: None,
+                    },
+                    Err(e) => ProfileTestResult {
+                        title,
+                        parsed: None,
+                        error: Some(e.to_string()),
+                    },
+                }
+            })
+            .collect(),
+    ))
+}
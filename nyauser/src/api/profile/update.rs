@@ -14,6 +14,8 @@ pub(super) async fn update(
             name, body.name
         )));
     }
+    body.validate()
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
     state
         .database
         .save_profile(&body)
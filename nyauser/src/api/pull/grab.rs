@@ -0,0 +1,42 @@
+use axum::extract::State;
+
+use super::*;
+
+pub(super) async fn grab(
+    _: Auth,
+    State(state): State<AppState>,
+    Json(body): Json<GrabRequest>,
+) -> ApiResult<Json<PullEntryNamed>> {
+    let Some(series) = state
+        .database
+        .get_series(&body.series)
+        .map_err(ApiError::Other)?
+    else {
+        return Err(ApiError::NotFound);
+    };
+    let profile = state
+        .searcher
+        .get_series_profile(&series)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let parsed = state
+        .searcher
+        .parse_result(&series, &profile, body.result)
+        .map_err(|e| ApiError::BadRequest(format!("failed to parse title: {e}")))?;
+    let key = parsed.key();
+    if let Some(reason) = state
+        .searcher
+        .check_grab(&series, &parsed)
+        .map_err(ApiError::Other)?
+    {
+        return Err(ApiError::BadRequest(reason.to_string()));
+    }
+    let Some(pull_entry) = state.searcher.grab(parsed).await.map_err(ApiError::Other)? else {
+        return Err(ApiError::BadRequest(format!(
+            "torrent already present in sink: {key}"
+        )));
+    };
+    Ok(Json(PullEntryNamed {
+        id: pull_entry.key(),
+        pull_entry,
+    }))
+}
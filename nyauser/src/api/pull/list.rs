@@ -1,5 +1,7 @@
 use axum::extract::{Query, State};
 
+use crate::db::PullQuery;
+
 use super::*;
 
 pub(super) async fn list(
@@ -7,14 +9,19 @@ pub(super) async fn list(
     State(state): State<AppState>,
     Query(filter): Query<PullEntryFilter>,
 ) -> ApiResult<Json<Vec<PullEntryNamed>>> {
-    let pulls = if filter.state == Some(PullState::Downloading) {
-        state
-            .database
-            .list_pull_entry_downloading()
-            .map_err(ApiError::Other)?
-    } else {
-        state.database.list_pull_entry().map_err(ApiError::Other)?
-    };
+    let mut pulls = state
+        .database
+        .list_pull_entry_query(&PullQuery {
+            title: filter.title_is.as_deref(),
+            profile: filter.profile.as_deref(),
+            state: filter.state,
+            ..Default::default()
+        })
+        .map_err(ApiError::Other)?;
+    // stale progress is better than none if the sink is unreachable
+    if let Err(e) = state.searcher.refresh_progress(&mut pulls).await {
+        warn!("failed to get live progress: {:?}", e);
+    }
     let raw: Vec<PullEntryNamed> = pulls
         .into_iter()
         .map(|pull_entry| PullEntryNamed {
@@ -22,11 +29,6 @@ pub(super) async fn list(
             pull_entry,
         })
         .filter(|entry| {
-            if let Some(profile) = &filter.profile {
-                if &entry.pull_entry.result.profile != profile {
-                    return false;
-                }
-            }
             if let Some(title_contains) = &filter.title_contains {
                 if !entry
                     .pull_entry
@@ -38,11 +40,6 @@ pub(super) async fn list(
                     return false;
                 }
             }
-            if let Some(title_is) = &filter.title_is {
-                if &entry.pull_entry.result.parsed.title != title_is {
-                    return false;
-                }
-            }
             if let Some(season_is) = filter.season_is {
                 if entry.pull_entry.result.parsed.season != season_is {
                     return false;
@@ -53,11 +50,6 @@ pub(super) async fn list(
                     return false;
                 }
             }
-            if let Some(state) = filter.state {
-                if entry.pull_entry.state != state {
-                    return false;
-                }
-            }
             true
         })
         .collect();
@@ -1,10 +1,14 @@
 use super::*;
 
 mod delete;
+mod grab;
 mod list;
+mod retry;
 
 pub fn route() -> Router<AppState> {
     Router::new()
         .route("/", routing::get(list::list))
+        .route("/grab", routing::post(grab::grab))
+        .route("/retry", routing::get(retry::list))
         .route("/:name", routing::delete(delete::delete))
 }
@@ -0,0 +1,21 @@
+use axum::extract::State;
+
+use super::*;
+
+/// pull entries whose push to the sink failed and will be retried
+pub(super) async fn list(
+    _: Auth,
+    State(state): State<AppState>,
+) -> ApiResult<Json<Vec<PullEntryNamed>>> {
+    let pulls = state.database.list_pull_entry().map_err(ApiError::Other)?;
+    Ok(Json(
+        pulls
+            .into_iter()
+            .filter(|pull_entry| pull_entry.retry.is_some())
+            .map(|pull_entry| PullEntryNamed {
+                id: pull_entry.key(),
+                pull_entry,
+            })
+            .collect(),
+    ))
+}
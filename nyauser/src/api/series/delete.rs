@@ -11,5 +11,9 @@ pub(super) async fn delete(
         .database
         .delete_series(&name)
         .map_err(ApiError::Other)?;
+    state
+        .database
+        .delete_decisions(&name)
+        .map_err(ApiError::Other)?;
     Ok(())
 }
@@ -10,10 +10,13 @@ pub(super) async fn get_status(
     let Some(series) = state.database.get_series(&name).map_err(ApiError::Other)? else {
         return Err(ApiError::NotFound);
     };
-    Ok(Json(
-        state
-            .database
-            .series_status(series)
-            .map_err(ApiError::Other)?,
-    ))
+    let mut pulls = state
+        .database
+        .list_pull_entry_series(&series.name)
+        .map_err(ApiError::Other)?;
+    // stale progress is better than none if the sink is unreachable
+    if let Err(e) = state.searcher.refresh_progress(&mut pulls).await {
+        warn!("failed to get live progress for '{}': {:?}", series.name, e);
+    }
+    Ok(Json(SeriesStatus::new(series, pulls)))
 }
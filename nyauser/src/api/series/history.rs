@@ -0,0 +1,23 @@
+use axum::extract::{Path, State};
+
+use super::*;
+
+pub(super) async fn history(
+    _: Auth,
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> ApiResult<Json<Vec<DecisionRecord>>> {
+    if state
+        .database
+        .get_series(&name)
+        .map_err(ApiError::Other)?
+        .is_none()
+    {
+        return Err(ApiError::NotFound);
+    }
+    state
+        .database
+        .list_decisions(&name)
+        .map_err(ApiError::Other)
+        .map(Json)
+}
@@ -3,7 +3,9 @@ use super::*;
 mod delete;
 mod get;
 mod get_status;
+mod history;
 mod list;
+mod search;
 mod update;
 
 pub fn route() -> Router<AppState> {
@@ -11,6 +13,8 @@ pub fn route() -> Router<AppState> {
         .route("/", routing::get(list::list))
         .route("/:name", routing::get(get::get))
         .route("/:name/status", routing::get(get_status::get_status))
+        .route("/:name/search", routing::get(search::search))
+        .route("/:name/history", routing::get(history::history))
         .route("/:name", routing::post(update::update))
         .route("/:name", routing::delete(delete::delete))
 }
@@ -0,0 +1,19 @@
+use axum::extract::{Path, State};
+
+use super::*;
+
+pub(super) async fn search(
+    _: Auth,
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> ApiResult<Json<Vec<SearchCandidate>>> {
+    let Some(series) = state.database.get_series(&name).map_err(ApiError::Other)? else {
+        return Err(ApiError::NotFound);
+    };
+    state
+        .searcher
+        .evaluate(&series)
+        .await
+        .map_err(ApiError::Other)
+        .map(Json)
+}
@@ -4,7 +4,7 @@ use indexmap::IndexMap;
 use nyauser_types::{Profile, Series};
 use serde::Deserialize;
 
-use crate::{search::SearchConfig, sink::SinkConfig, source::SourceConfig};
+use crate::{db::DbBackend, search::SearchConfig, sink::SinkConfig, source::SourceConfig};
 
 #[derive(Deserialize)]
 pub struct Config {
@@ -18,6 +18,8 @@ pub struct Config {
     pub search: SearchConfig,
     pub db_file: String,
     #[serde(default)]
+    pub db_backend: DbBackend,
+    #[serde(default)]
     pub profiles: Vec<Profile>,
     #[serde(default)]
     pub series: Vec<Series>,
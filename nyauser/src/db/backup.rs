@@ -0,0 +1,163 @@
+use chrono::Utc;
+use nyauser_types::{Backup, RestoreMode, RestoreReport, BACKUP_VERSION};
+
+use anyhow::Result;
+
+use super::{storage::Kind, Database};
+
+impl Database {
+    pub fn export(&self) -> Result<Backup> {
+        Ok(Backup {
+            version: BACKUP_VERSION,
+            created: Utc::now(),
+            profiles: self.list_profile()?,
+            series: self.list_series()?,
+            pull_entries: self.list_pull_entry()?,
+            blocklist: self.list_block()?,
+        })
+    }
+
+    pub fn restore(&self, backup: Backup, mode: RestoreMode) -> Result<RestoreReport> {
+        if backup.version > BACKUP_VERSION {
+            bail!(
+                "backup version {} is newer than this version of nyauser supports ({BACKUP_VERSION})",
+                backup.version
+            );
+        }
+        let mut report = RestoreReport::default();
+        if mode == RestoreMode::Replace {
+            for kind in Kind::ALL {
+                for (key, _) in self.storage.list(kind)? {
+                    self.storage.remove(kind, &key)?;
+                    report.removed += 1;
+                }
+            }
+            for (key, _) in self.storage.list_pulls(&Default::default())? {
+                self.storage.remove_pull(&key)?;
+                report.removed += 1;
+            }
+        }
+        for profile in &backup.profiles {
+            self.save_profile(profile)?;
+            report.profiles += 1;
+        }
+        for series in &backup.series {
+            self.save_series(series)?;
+            report.series += 1;
+        }
+        for pull_entry in &backup.pull_entries {
+            self.save_pull(pull_entry)?;
+            report.pull_entries += 1;
+        }
+        for block in &backup.blocklist {
+            self.save_block(block)?;
+            report.blocklist += 1;
+        }
+        self.storage.flush()?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nyauser_types::{BlockEntry, BlockRule, PullState};
+    use serde_json::json;
+
+    use super::{
+        super::{
+            testing::{pull, series, temporary},
+            DbBackend,
+        },
+        *,
+    };
+
+    /// sorted profile, series and pull entry keys
+    fn keys(db: &Database) -> (Vec<String>, Vec<String>, Vec<String>) {
+        let mut series: Vec<_> = db
+            .list_series()
+            .unwrap()
+            .into_iter()
+            .map(|x| x.name)
+            .collect();
+        let mut pulls: Vec<_> = db
+            .list_pull_entry()
+            .unwrap()
+            .iter()
+            .map(|x| x.key())
+            .collect();
+        let mut profiles: Vec<_> = db
+            .list_profile()
+            .unwrap()
+            .into_iter()
+            .map(|x| x.name)
+            .collect();
+        series.sort();
+        pulls.sort();
+        profiles.sort();
+        (profiles, series, pulls)
+    }
+
+    #[test]
+    fn test_export_restore() {
+        let source = temporary(DbBackend::Sled);
+        source
+            .save_profile(
+                &serde_json::from_value(json!({
+                    "name": "group",
+                    "search_prefix": null,
+                    "parser": "auto",
+                    "relocate": null,
+                }))
+                .unwrap(),
+            )
+            .unwrap();
+        source.save_series(&series("Foo")).unwrap();
+        source
+            .save_pull(&pull("Foo", Some("Foo"), 1, PullState::Finished, None))
+            .unwrap();
+        source
+            .save_block(&BlockEntry::new(BlockRule::Hash("hash".to_string()), None))
+            .unwrap();
+
+        for (mode, backend, expected_removed) in [
+            (RestoreMode::Merge, DbBackend::Sled, 0),
+            (RestoreMode::Replace, DbBackend::Sqlite, 2),
+        ] {
+            let target = temporary(backend);
+            target.save_series(&series("Bar")).unwrap();
+            target
+                .save_pull(&pull("Bar", Some("Bar"), 1, PullState::Finished, None))
+                .unwrap();
+
+            let report = target.restore(source.export().unwrap(), mode).unwrap();
+            assert_eq!(report.removed, expected_removed);
+            assert_eq!(
+                (
+                    report.profiles,
+                    report.series,
+                    report.pull_entries,
+                    report.blocklist
+                ),
+                (1, 1, 1, 1)
+            );
+            let (profiles, series, pulls) = keys(&target);
+            assert_eq!(profiles, vec!["group"]);
+            if mode == RestoreMode::Merge {
+                assert_eq!(series, vec!["Bar", "Foo"]);
+                assert_eq!(pulls, vec!["Bar_S01E01", "Foo_S01E01"]);
+            } else {
+                assert_eq!(series, vec!["Foo"]);
+                assert_eq!(pulls, vec!["Foo_S01E01"]);
+            }
+            assert_eq!(target.list_block().unwrap().len(), 1);
+            let restored = target.get_pull_entry("Foo_S01E01").unwrap().unwrap();
+            assert_eq!(restored.torrent_hash, "hash-Foo-1");
+        }
+
+        let mut newer = source.export().unwrap();
+        newer.version = BACKUP_VERSION + 1;
+        assert!(temporary(DbBackend::Sled)
+            .restore(newer, RestoreMode::Merge)
+            .is_err());
+    }
+}
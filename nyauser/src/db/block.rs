@@ -0,0 +1,24 @@
+use nyauser_types::BlockEntry;
+
+use anyhow::Result;
+
+use super::{storage::Kind, Database};
+
+impl Database {
+    pub fn save_block(&self, block: &BlockEntry) -> Result<()> {
+        self.storage
+            .put(Kind::Block, &block.id(), &serde_json::to_vec(block)?)
+    }
+
+    pub fn delete_block(&self, id: &str) -> Result<()> {
+        self.storage.remove(Kind::Block, id)
+    }
+
+    pub fn get_block(&self, id: &str) -> Result<Option<BlockEntry>> {
+        self.get_serde(Kind::Block, id)
+    }
+
+    pub fn list_block(&self) -> Result<Vec<BlockEntry>> {
+        self.list_serde(Kind::Block)
+    }
+}
@@ -0,0 +1,43 @@
+use nyauser_types::DecisionRecord;
+
+use super::Database;
+use anyhow::Result;
+
+impl Database {
+    /// records decisions for a series, dropping the oldest beyond `cap`
+    pub fn save_decisions(
+        &self,
+        series: &str,
+        records: &[DecisionRecord],
+        cap: usize,
+    ) -> Result<()> {
+        for (i, record) in records.iter().enumerate() {
+            // sequences sort oldest first
+            let seq = format!("{:020}-{i:06}", record.at.timestamp_nanos());
+            self.storage
+                .put_decision(series, &seq, &serde_json::to_vec(record)?)?;
+        }
+        let seqs = self.storage.list_decisions(series)?;
+        if seqs.len() > cap {
+            for (seq, _) in &seqs[..seqs.len() - cap] {
+                self.storage.remove_decision(series, seq)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn list_decisions(&self, series: &str) -> Result<Vec<DecisionRecord>> {
+        let mut out = vec![];
+        for (_, value) in self.storage.list_decisions(series)? {
+            out.push(serde_json::from_slice(&value)?);
+        }
+        Ok(out)
+    }
+
+    pub fn delete_decisions(&self, series: &str) -> Result<()> {
+        for (seq, _) in self.storage.list_decisions(series)? {
+            self.storage.remove_decision(series, &seq)?;
+        }
+        Ok(())
+    }
+}
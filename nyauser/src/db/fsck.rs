@@ -0,0 +1,273 @@
+use std::collections::{HashMap, HashSet};
+
+use nyauser_types::{
+    BlockEntry, DecisionRecord, FsckFinding, FsckIssue, Profile, PullEntry, Series,
+};
+use serde::de::DeserializeOwned;
+
+use super::{storage::Kind, Database};
+use anyhow::Result;
+
+fn check<T: DeserializeOwned>(value: &[u8]) -> Option<String> {
+    serde_json::from_slice::<T>(value)
+        .err()
+        .map(|e| e.to_string())
+}
+
+fn check_kind(kind: Kind, value: &[u8]) -> Option<String> {
+    match kind {
+        Kind::Series => check::<Series>(value),
+        Kind::Profile => check::<Profile>(value),
+        Kind::Block => check::<BlockEntry>(value),
+    }
+}
+
+impl Database {
+    /// Checks records and indexes for consistency, repairing what can be if `repair` is set
+    pub fn fsck(&self, repair: bool) -> Result<Vec<FsckFinding>> {
+        let mut findings = vec![];
+        let mut report = |issue: FsckIssue, repaired: bool| {
+            warn!("fsck: {issue}{}", if repaired { " (repaired)" } else { "" });
+            findings.push(FsckFinding { issue, repaired });
+        };
+
+        for kind in Kind::ALL {
+            for (key, value) in self.storage.list(kind)? {
+                let Some(error) = check_kind(kind, &value) else {
+                    continue;
+                };
+                let name = format!("{}-{key}", kind.as_str());
+                if repair {
+                    self.storage.quarantine(&name, &value)?;
+                    self.storage.remove(kind, &key)?;
+                }
+                report(FsckIssue::Corrupt { key: name, error }, repair);
+            }
+        }
+        for (key, value) in self.storage.list_pulls(&Default::default())? {
+            let Some(error) = check::<PullEntry>(&value) else {
+                continue;
+            };
+            if repair {
+                self.storage.quarantine(&format!("torrent-{key}"), &value)?;
+                self.storage.remove_pull(&key)?;
+            }
+            report(
+                FsckIssue::Corrupt {
+                    key: format!("torrent-{key}"),
+                    error,
+                },
+                repair,
+            );
+        }
+        for (series, seq, value) in self.storage.list_all_decisions()? {
+            let Some(error) = check::<DecisionRecord>(&value) else {
+                continue;
+            };
+            let key = format!("decisions/{series}-{seq}");
+            if repair {
+                self.storage.quarantine(&key, &value)?;
+                self.storage.remove_decision(&series, &seq)?;
+            }
+            report(FsckIssue::Corrupt { key, error }, repair);
+        }
+
+        let pulls: HashMap<String, PullEntry> = self
+            .list_pull_entry()?
+            .into_iter()
+            .map(|x| (x.key(), x))
+            .collect();
+        let mut indexed = HashSet::new();
+        for (torrent_id, key) in self.storage.torrent_index()? {
+            let issue = match pulls.get(&key) {
+                None => FsckIssue::DanglingIndex { torrent_id, key },
+                Some(pull) if pull.torrent_id != Some(torrent_id) => {
+                    FsckIssue::MismatchedIndex { torrent_id, key }
+                }
+                Some(_) => {
+                    indexed.insert(key);
+                    continue;
+                }
+            };
+            if repair {
+                self.storage.remove_torrent_index(torrent_id)?;
+            }
+            report(issue, repair);
+        }
+        for (key, pull) in &pulls {
+            let Some(torrent_id) = pull.torrent_id else {
+                continue;
+            };
+            if indexed.contains(key) {
+                continue;
+            }
+            if repair {
+                self.save_pull(pull)?;
+            }
+            report(
+                FsckIssue::UnindexedTorrent {
+                    torrent_id,
+                    key: key.clone(),
+                },
+                repair,
+            );
+        }
+
+        let profiles: HashSet<String> = self.list_profile()?.into_iter().map(|x| x.name).collect();
+        let series: HashMap<String, Series> = self
+            .list_series()?
+            .into_iter()
+            .map(|x| (x.name.clone(), x))
+            .collect();
+        for series in series.values() {
+            if !profiles.contains(&series.profile) {
+                report(
+                    FsckIssue::MissingProfile {
+                        series: series.name.clone(),
+                        profile: series.profile.clone(),
+                    },
+                    false,
+                );
+            }
+        }
+        for (key, pull) in pulls {
+            let Some(name) = &pull.result.series else {
+                continue;
+            };
+            if series.contains_key(name) {
+                continue;
+            }
+            let series = name.clone();
+            if repair {
+                self.delete_pull(pull)?;
+            }
+            report(FsckIssue::OrphanedPull { key, series }, repair);
+        }
+        Ok(findings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nyauser_types::PullState;
+
+    use super::{
+        super::{
+            testing::{pull, series, temporary},
+            DbBackend,
+        },
+        *,
+    };
+
+    fn issues(findings: &[FsckFinding]) -> Vec<(FsckIssue, bool)> {
+        findings
+            .iter()
+            .map(|x| (x.issue.clone(), x.repaired))
+            .collect()
+    }
+
+    #[test]
+    fn test_fsck() {
+        let db = temporary(DbBackend::Sled);
+        // uses the missing `group` profile
+        db.save_series(&series("Foo")).unwrap();
+        db.storage.put(Kind::Series, "Bad", b"{").unwrap();
+        let corrupt = pull("Foo", Some("Foo"), 3, PullState::Finished, None);
+        db.storage
+            .save_pull_at("Foo_S01E03", &corrupt, b"[")
+            .unwrap();
+        // a record that can't tell its torrent id leaves the index behind when removed
+        let dangling = pull("Foo", Some("Foo"), 1, PullState::Downloading, Some(7));
+        db.storage
+            .save_pull_at("Foo_S01E01", &dangling, b"{")
+            .unwrap();
+        db.storage.remove_pull("Foo_S01E01").unwrap();
+        let unindexed = pull("Foo", Some("Foo"), 2, PullState::Downloading, Some(8));
+        let without_id = pull("Foo", Some("Foo"), 2, PullState::Downloading, None);
+        db.storage
+            .save_pull_at(
+                "Foo_S01E02",
+                &without_id,
+                &serde_json::to_vec(&unindexed).unwrap(),
+            )
+            .unwrap();
+        db.save_pull(&pull("Bar", Some("Bar"), 1, PullState::Finished, None))
+            .unwrap();
+
+        let expected = |repaired: bool| {
+            vec![
+                (
+                    FsckIssue::Corrupt {
+                        key: "series-Bad".to_string(),
+                        error: check::<Series>(b"{").unwrap(),
+                    },
+                    repaired,
+                ),
+                (
+                    FsckIssue::Corrupt {
+                        key: "torrent-Foo_S01E03".to_string(),
+                        error: check::<PullEntry>(b"[").unwrap(),
+                    },
+                    repaired,
+                ),
+                (
+                    FsckIssue::DanglingIndex {
+                        torrent_id: 7,
+                        key: "Foo_S01E01".to_string(),
+                    },
+                    repaired,
+                ),
+                (
+                    FsckIssue::UnindexedTorrent {
+                        torrent_id: 8,
+                        key: "Foo_S01E02".to_string(),
+                    },
+                    repaired,
+                ),
+                (
+                    FsckIssue::MissingProfile {
+                        series: "Foo".to_string(),
+                        profile: "group".to_string(),
+                    },
+                    false,
+                ),
+                (
+                    FsckIssue::OrphanedPull {
+                        key: "Bar_S01E01".to_string(),
+                        series: "Bar".to_string(),
+                    },
+                    repaired,
+                ),
+            ]
+        };
+
+        // only reports
+        assert_eq!(issues(&db.fsck(false).unwrap()), expected(false));
+        assert_eq!(issues(&db.fsck(false).unwrap()), expected(false));
+
+        assert_eq!(issues(&db.fsck(true).unwrap()), expected(true));
+        assert!(db.storage.get(Kind::Series, "Bad").unwrap().is_none());
+        assert!(db.storage.get_pull("Foo_S01E03").unwrap().is_none());
+        assert_eq!(
+            db.storage.torrent_index().unwrap(),
+            vec![(8, "Foo_S01E02".to_string())]
+        );
+        assert!(db.get_pull_entry("Bar_S01E01").unwrap().is_none());
+        assert_eq!(
+            db.get_pull_entry("Foo_S01E02").unwrap().unwrap().torrent_id,
+            Some(8)
+        );
+
+        // the missing profile is left to the user
+        assert_eq!(
+            issues(&db.fsck(true).unwrap()),
+            vec![(
+                FsckIssue::MissingProfile {
+                    series: "Foo".to_string(),
+                    profile: "group".to_string(),
+                },
+                false,
+            )]
+        );
+    }
+}
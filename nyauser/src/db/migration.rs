@@ -0,0 +1,401 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use chrono::Utc;
+use nyauser_types::{BlockEntry, BlockRule, Episode, Profile, PullEntry, Series};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+
+use super::{parse_keyed, storage::Kind, Database};
+
+pub(super) const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+struct Migration {
+    /// schema version after this migration ran
+    version: u32,
+    name: &'static str,
+    run: fn(&Database) -> Result<()>,
+}
+
+/// In order of `version`. Databases from before versioning are version 0.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "fill in defaults for fields added to stored records",
+        run: fill_defaults,
+    },
+    Migration {
+        version: 2,
+        name: "link pull entries to their series and key them by it",
+        run: key_by_series,
+    },
+    Migration {
+        version: 3,
+        name: "key pull entries and episode blocks by structured episodes",
+        run: key_by_episode,
+    },
+    Migration {
+        version: 4,
+        name: "move well-known release attributes out of ext",
+        run: type_release_attributes,
+    },
+];
+
+fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|x| x.version).unwrap_or(0)
+}
+
+/// Round trips every record through its current type, so later migrations see complete records
+fn fill_defaults(db: &Database) -> Result<()> {
+    for (_, pull) in stored_pulls(db)? {
+        db.save_pull(&pull)?;
+    }
+    reencode::<Series>(db, Kind::Series)?;
+    reencode::<Profile>(db, Kind::Profile)?;
+    reencode::<BlockEntry>(db, Kind::Block)?;
+    Ok(())
+}
+
+/// Entries from before the series was recorded are matched to one by their parsed title, and
+/// every entry is moved to its series namespaced key. Episode block rules hold keys too, so
+/// their title part is matched the same way.
+fn key_by_series(db: &Database) -> Result<()> {
+    let series = db.list_series()?;
+    let find_series = |title: &str| {
+        series
+            .iter()
+            .find(|x| x.name == title)
+            .or_else(|| series.iter().find(|x| x.matches_title(title)))
+            .map(|x| x.name.clone())
+    };
+    rekey(db, find_series)?;
+    rewrite_episode_blocks(db, |old| {
+        let (title, rest) = old.rsplit_once("_S")?;
+        Some(format!("{}_S{rest}", find_series(title)?))
+    })
+}
+
+/// Episode numbers in keys are now padded, and specials have their own form. Episode block
+/// rules hold keys too, so they are rewritten by parsing the old key.
+fn key_by_episode(db: &Database) -> Result<()> {
+    rekey(db, |_| None)?;
+    rewrite_episode_blocks(db, old_episode_key)
+}
+
+/// Replaces the key of every episode block with `rewrite` of it, leaving blocks it returns None for
+fn rewrite_episode_blocks(db: &Database, rewrite: impl Fn(&str) -> Option<String>) -> Result<()> {
+    for block in db.list_block()? {
+        let BlockRule::Episode(old) = &block.rule else {
+            continue;
+        };
+        let Some(key) = rewrite(old) else {
+            warn!("leaving episode block '{old}', no key found for it");
+            continue;
+        };
+        if &key != old {
+            db.delete_block(&block.id())?;
+            db.save_block(&BlockEntry {
+                rule: BlockRule::Episode(key),
+                ..block
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Resolution, codecs, source, group, version and language captures used to be kept in `ext`.
+/// Values that don't parse stay there.
+fn type_release_attributes(db: &Database) -> Result<()> {
+    for (_, mut pull) in stored_pulls(db)? {
+        let parsed = &mut pull.result.parsed;
+        for (name, value) in std::mem::take(&mut parsed.ext) {
+            if !parsed.set_attribute(&name, &value) {
+                parsed.ext.insert(name, value);
+            }
+        }
+        db.save_pull(&pull)?;
+    }
+    Ok(())
+}
+
+/// `<name>_S<season>E<episode>`, from before episodes were structured
+fn old_episode_key(key: &str) -> Option<String> {
+    let (name, rest) = key.rsplit_once("_S")?;
+    let (season, episode) = rest.split_once('E')?;
+    let season: u32 = season.parse().ok()?;
+    let episode: Episode = episode.parse().ok()?;
+    Some(format!("{name}_S{season:02}{}", episode.key()))
+}
+
+/// Moves every entry to its current key, after `find_series` fills in a missing series.
+/// When two entries end up on the same episode, one that is done or in progress is kept
+/// over one that may be replaced, then the one already at the key, then the first found.
+/// Every removal happens before any entry is saved, so no entry is written over by a stale
+/// record of the same key.
+fn rekey(db: &Database, find_series: impl Fn(&str) -> Option<String>) -> Result<()> {
+    // current key -> (stored key, entry)
+    let mut kept: BTreeMap<String, (String, PullEntry)> = BTreeMap::new();
+    let mut removed = vec![];
+    for (stored_key, mut pull) in stored_pulls(db)? {
+        if pull.result.series.is_none() {
+            pull.result.series = find_series(&pull.result.parsed.title);
+            if pull.result.series.is_none() {
+                warn!("no series found for '{stored_key}', leaving it keyed by title");
+            }
+        }
+        let key = pull.key();
+        let Some((other_key, other)) = kept.remove(&key) else {
+            kept.insert(key, (stored_key, pull));
+            continue;
+        };
+        let replaces = if other.is_replaceable() != pull.is_replaceable() {
+            other.is_replaceable()
+        } else {
+            stored_key == key
+        };
+        let (winner, loser) = if replaces {
+            ((stored_key, pull), other_key)
+        } else {
+            ((other_key, other), stored_key)
+        };
+        warn!("dropping '{loser}', '{}' is kept as '{key}'", winner.0);
+        removed.push(loser);
+        kept.insert(key, winner);
+    }
+    for (key, (stored_key, _)) in &kept {
+        if stored_key != key {
+            removed.push(stored_key.clone());
+        }
+    }
+    for key in removed {
+        db.storage.remove_pull(&key)?;
+    }
+    for (_, pull) in kept.into_values() {
+        db.save_pull(&pull)?;
+    }
+    Ok(())
+}
+
+/// Corrupt records are left untouched, and reported
+fn reencode<T: DeserializeOwned + Serialize>(db: &Database, kind: Kind) -> Result<()> {
+    for (key, parsed) in parse_keyed::<T>(kind.as_str(), db.storage.list(kind)?) {
+        db.storage.put(kind, &key, &serde_json::to_vec(&parsed)?)?;
+    }
+    Ok(())
+}
+
+/// Every pull entry with the key it is stored under, which may be outdated. Corrupt records
+/// are left untouched.
+fn stored_pulls(db: &Database) -> Result<Vec<(String, PullEntry)>> {
+    Ok(parse_keyed(
+        "torrent",
+        db.storage.list_pulls(&Default::default())?,
+    ))
+}
+
+impl Database {
+    pub fn schema_version(&self) -> Result<u32> {
+        let Some(raw) = self.storage.get_meta(SCHEMA_VERSION_KEY)? else {
+            return Ok(0);
+        };
+        Ok(raw.parse()?)
+    }
+
+    /// Runs pending migrations, writing a backup to `backup_prefix-v{version}-{timestamp}.json` first
+    pub fn migrate(&self, backup_prefix: &Path) -> Result<()> {
+        let latest = latest_version();
+        if self.storage.is_empty()? {
+            self.storage
+                .set_meta(SCHEMA_VERSION_KEY, &latest.to_string())?;
+            return Ok(());
+        }
+        let version = self.schema_version()?;
+        if version > latest {
+            bail!("database schema v{version} is newer than this version of nyauser supports (v{latest})");
+        }
+        if version == latest {
+            return Ok(());
+        }
+        let backup = self.backup_raw(backup_prefix, version)?;
+        info!(
+            "migrating database from schema v{version} to v{latest}, backup at {}",
+            backup.display()
+        );
+        for migration in MIGRATIONS.iter().filter(|x| x.version > version) {
+            info!(
+                "running migration v{}: {}",
+                migration.version, migration.name
+            );
+            (migration.run)(self)
+                .map_err(|e| anyhow!("migration v{} failed: {e:?}", migration.version))?;
+            self.storage
+                .set_meta(SCHEMA_VERSION_KEY, &migration.version.to_string())?;
+        }
+        self.storage.flush()?;
+        Ok(())
+    }
+
+    /// Dumps every record as-is. Values are expected to be UTF-8 JSON, anything else is stored lossily.
+    fn backup_raw(&self, backup_prefix: &Path, version: u32) -> Result<PathBuf> {
+        let mut path = backup_prefix.as_os_str().to_owned();
+        path.push(format!(
+            "-v{version}-{}.json",
+            Utc::now().format("%Y%m%dT%H%M%S")
+        ));
+        let path = PathBuf::from(path);
+        let entries = self
+            .dump()?
+            .into_iter()
+            .map(|(key, value)| json!({ "key": key, "value": value }))
+            .collect::<Vec<_>>();
+        std::fs::write(
+            &path,
+            serde_json::to_vec_pretty(&json!({ "version": version, "entries": entries }))?,
+        )?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nyauser_types::PullState;
+
+    use super::*;
+    use crate::db::{
+        testing::{pull, series, temporary},
+        DbBackend,
+    };
+
+    fn store(db: &Database, key: &str, pull: &PullEntry) {
+        db.storage
+            .save_pull_at(key, pull, &serde_json::to_vec(pull).unwrap())
+            .unwrap();
+    }
+
+    fn keys(db: &Database) -> Vec<String> {
+        db.storage
+            .list_pulls(&Default::default())
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// An entry moved onto a key earlier in the pass must not be overwritten by the stale record
+    /// stored at that key. `FOO_S01E01` sorts before `Foo_S01E01`, so it is moved first.
+    #[test]
+    fn test_rekey_keeps_moved_entry() {
+        for backend in [DbBackend::Sled, DbBackend::Sqlite] {
+            let db = temporary(backend);
+            db.save_series(&series("Foo")).unwrap();
+            store(
+                &db,
+                "FOO_S01E01",
+                &pull("FOO", None, 1, PullState::Downloading, Some(7)),
+            );
+            store(
+                &db,
+                "Foo_S01E01",
+                &pull("Foo", None, 1, PullState::Failed, None),
+            );
+            key_by_series(&db).unwrap();
+            assert_eq!(keys(&db), vec!["Foo_S01E01"], "{backend:?}");
+            let kept = db.get_pull_entry("Foo_S01E01").unwrap().unwrap();
+            assert_eq!(kept.state, PullState::Downloading, "{backend:?}");
+            assert_eq!(kept.result.series.as_deref(), Some("Foo"));
+            let indexed = db.get_pull_entry_from_torrent_id(7).unwrap().unwrap();
+            assert_eq!(indexed.key(), "Foo_S01E01", "{backend:?}");
+        }
+    }
+
+    #[test]
+    fn test_key_by_series_rewrites_episode_blocks() {
+        let db = temporary(DbBackend::Sled);
+        db.save_series(&series("Foo")).unwrap();
+        for key in ["FOO_S1E7", "Unknown_S1E7"] {
+            db.save_block(&BlockEntry::new(BlockRule::Episode(key.to_string()), None))
+                .unwrap();
+        }
+        key_by_series(&db).unwrap();
+        let mut blocks = db
+            .list_block()
+            .unwrap()
+            .into_iter()
+            .map(|x| x.rule.value().to_string())
+            .collect::<Vec<_>>();
+        blocks.sort();
+        assert_eq!(blocks, vec!["Foo_S1E7", "Unknown_S1E7"]);
+    }
+
+    /// A database from before versioning, with title keyed entries and unpadded episodes, ends
+    /// up keyed by series and padded episode with its attributes typed.
+    #[test]
+    fn test_migrate_from_v0() {
+        for backend in [DbBackend::Sled, DbBackend::Sqlite] {
+            let db = temporary(backend);
+            let dir = tempfile::tempdir().unwrap();
+            db.save_series(&series("Foo")).unwrap();
+            store(
+                &db,
+                "FOO_S1E7",
+                &pull("FOO", None, 7, PullState::Finished, None),
+            );
+            let mut downloading = pull("Foo", None, 8, PullState::Downloading, Some(3));
+            downloading
+                .result
+                .parsed
+                .ext
+                .insert("resolution".to_string(), "1080p".to_string());
+            downloading
+                .result
+                .parsed
+                .ext
+                .insert("source".to_string(), "VHS".to_string());
+            store(&db, "Foo_S1E8", &downloading);
+            db.save_block(&BlockEntry::new(
+                BlockRule::Episode("FOO_S1E7".to_string()),
+                None,
+            ))
+            .unwrap();
+            assert_eq!(db.schema_version().unwrap(), 0);
+
+            let backup_prefix = dir.path().join("backup");
+            db.migrate(&backup_prefix).unwrap();
+            assert_eq!(db.schema_version().unwrap(), latest_version());
+            assert_eq!(keys(&db), vec!["Foo_S01E07", "Foo_S01E08"], "{backend:?}");
+            let blocks = db
+                .list_block()
+                .unwrap()
+                .into_iter()
+                .map(|x| x.rule.value().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(blocks, vec!["Foo_S01E07"], "{backend:?}");
+            let migrated = db.get_pull_entry_from_torrent_id(3).unwrap().unwrap();
+            assert_eq!(migrated.key(), "Foo_S01E08");
+            assert_eq!(migrated.result.series.as_deref(), Some("Foo"));
+            assert_eq!(migrated.result.parsed.resolution, Some(1080));
+            // values that don't fit their field stay in ext
+            assert_eq!(migrated.result.parsed.source, None);
+            assert_eq!(
+                migrated.result.parsed.ext.get("source").map(String::as_str),
+                Some("VHS")
+            );
+
+            let backups = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|x| x.unwrap().path())
+                .collect::<Vec<_>>();
+            assert_eq!(backups.len(), 1);
+            let backup: serde_json::Value =
+                serde_json::from_slice(&std::fs::read(&backups[0]).unwrap()).unwrap();
+            assert_eq!(backup["version"], 0);
+
+            // already at the latest version, nothing to back up
+            db.migrate(&backup_prefix).unwrap();
+            assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        }
+    }
+}
@@ -1,6 +1,23 @@
+use std::path::Path;
+
 use anyhow::Result;
-use serde::de::DeserializeOwned;
-use sled::Db;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use self::{
+    sled_storage::SledStorage,
+    sqlite_storage::SqliteStorage,
+    storage::{Kind, Storage},
+};
+
+mod backup;
+
+mod block;
+
+mod decision;
+
+mod fsck;
+
+mod migration;
 
 mod pull_entry;
 
@@ -8,36 +25,268 @@ mod profile;
 
 mod series;
 
+mod sled_storage;
+
+mod sqlite_storage;
+
+mod storage;
+
+pub use self::storage::PullQuery;
+
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DbBackend {
+    /// `db_file` is a sled directory
+    #[default]
+    Sled,
+    /// `db_file` is a SQLite database
+    Sqlite,
+}
+
 pub struct Database {
-    db: Db,
+    storage: Box<dyn Storage>,
 }
 
 impl Database {
-    pub fn new(db: Db) -> Self {
-        Self { db }
+    pub fn open(backend: DbBackend, path: &Path) -> Result<Self> {
+        let storage: Box<dyn Storage> = match backend {
+            DbBackend::Sled => Box::new(SledStorage::new(sled::open(path)?)?),
+            DbBackend::Sqlite => Box::new(SqliteStorage::open(path)?),
+        };
+        Ok(Self { storage })
     }
 
     pub async fn flush(&self) -> Result<()> {
-        self.db.flush_async().await?;
-        Ok(())
+        self.storage.flush()
     }
 
-    fn get_serde<T: DeserializeOwned>(&self, prefix: &str, key: &str) -> Result<Option<T>> {
-        let Some(raw) = self.db.get(format!("{prefix}-{key}"))? else {
-            return Ok(None);
-        };
-        let utf = String::from_utf8(raw.to_vec())?;
-        Ok(Some(serde_json::from_str(&utf)?))
+    /// Copies every record, including decisions and corrupt ones, into `other`
+    pub fn copy_into(&self, other: &Database) -> Result<()> {
+        for kind in Kind::ALL {
+            for (key, value) in self.storage.list(kind)? {
+                other.storage.put(kind, &key, &value)?;
+            }
+        }
+        for (key, value) in self.storage.list_pulls(&Default::default())? {
+            match parse_record("torrent", &key, &value) {
+                Some(pull) => other.storage.save_pull(&pull, &value)?,
+                None => other
+                    .storage
+                    .quarantine(&format!("torrent-{key}"), &value)?,
+            }
+        }
+        for (series, seq, value) in self.storage.list_all_decisions()? {
+            other.storage.put_decision(&series, &seq, &value)?;
+        }
+        if let Some(version) = self.storage.get_meta(migration::SCHEMA_VERSION_KEY)? {
+            other
+                .storage
+                .set_meta(migration::SCHEMA_VERSION_KEY, &version)?;
+        }
+        other.storage.flush()
     }
 
-    fn list_serde<T: DeserializeOwned>(&self, prefix: &str) -> Result<Vec<T>> {
+    /// (`<kind>-<key>`, value) for every record, for `--dump`
+    pub fn dump(&self) -> Result<Vec<(String, String)>> {
         let mut out = vec![];
-        for entry in self.db.scan_prefix(prefix) {
-            let (_, value) = entry?;
-            let value = std::str::from_utf8(&value[..])?;
-            let pull_entry: T = serde_json::from_str(value)?;
-            out.push(pull_entry);
+        for kind in Kind::ALL {
+            for (key, value) in self.storage.list(kind)? {
+                out.push((format!("{}-{key}", kind.as_str()), lossy(value)));
+            }
+        }
+        for (key, value) in self.storage.list_pulls(&Default::default())? {
+            out.push((format!("torrent-{key}"), lossy(value)));
+        }
+        for (torrent_id, key) in self.storage.torrent_index()? {
+            out.push((format!("downloading-{torrent_id}"), key));
+        }
+        for (series, seq, value) in self.storage.list_all_decisions()? {
+            out.push((format!("decision-{series}-{seq}"), lossy(value)));
         }
         Ok(out)
     }
+
+    fn get_serde<T: DeserializeOwned>(&self, kind: Kind, key: &str) -> Result<Option<T>> {
+        let Some(raw) = self.storage.get(kind, key)? else {
+            return Ok(None);
+        };
+        Ok(Some(serde_json::from_slice(&raw)?))
+    }
+
+    fn list_serde<T: DeserializeOwned>(&self, kind: Kind) -> Result<Vec<T>> {
+        Ok(parse_all(kind.as_str(), self.storage.list(kind)?))
+    }
+}
+
+fn lossy(value: Vec<u8>) -> String {
+    String::from_utf8(value).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+/// Corrupt records are logged and skipped, so one bad record doesn't hide the rest
+fn parse_all<T: DeserializeOwned>(prefix: &str, records: Vec<(String, Vec<u8>)>) -> Vec<T> {
+    parse_keyed(prefix, records)
+        .into_iter()
+        .map(|(_, x)| x)
+        .collect()
+}
+
+/// `parse_all`, keeping each record's key
+fn parse_keyed<T: DeserializeOwned>(
+    prefix: &str,
+    records: Vec<(String, Vec<u8>)>,
+) -> Vec<(String, T)> {
+    records
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.clone(), parse_record(prefix, &key, &value)?)))
+        .collect()
+}
+
+/// None, after logging it, if the record is corrupt
+fn parse_record<T: DeserializeOwned>(prefix: &str, key: &str, value: &[u8]) -> Option<T> {
+    match serde_json::from_slice(value) {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            error!("corrupt record '{prefix}-{key}': {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use nyauser_types::{PullEntry, PullState, Series};
+    use serde_json::json;
+
+    use super::*;
+
+    /// an empty database that only lives as long as it
+    pub fn temporary(backend: DbBackend) -> Database {
+        let storage: Box<dyn Storage> = match backend {
+            DbBackend::Sled => Box::new(
+                SledStorage::new(sled::Config::new().temporary(true).open().unwrap()).unwrap(),
+            ),
+            DbBackend::Sqlite => Box::new(
+                SqliteStorage::with_connection(rusqlite::Connection::open_in_memory().unwrap())
+                    .unwrap(),
+            ),
+        };
+        Database { storage }
+    }
+
+    /// a series using the `group` profile, with defaults for everything else
+    pub fn series(name: &str) -> Series {
+        serde_json::from_value(json!({
+            "name": name,
+            "profile": "group",
+            "max_days_old": null,
+            "relocate": null,
+        }))
+        .unwrap()
+    }
+
+    /// a pull entry for episode `episode` of `title`, as nyauser stored it before series were
+    /// recorded if `series` is None
+    pub fn pull(
+        title: &str,
+        series: Option<&str>,
+        episode: u32,
+        state: PullState,
+        torrent_id: Option<i64>,
+    ) -> PullEntry {
+        serde_json::from_value(json!({
+            "result": {
+                "result": {
+                    "title": format!("[Group] {title} - {episode:02} (1080p).mkv"),
+                    "torrent_link": "",
+                    "view_link": "",
+                    "date": "2023-01-01T00:00:00+00:00",
+                    "seeders": 1,
+                    "leechers": 0,
+                    "downloads": 0,
+                    "size": 0,
+                },
+                "parsed": {
+                    "title": title,
+                    "season": 1,
+                    "episode": episode,
+                    "checksum": 0,
+                    "ext": {},
+                },
+                "profile": "group",
+                "series": series,
+                "relocate": null,
+                "relocate_season": true,
+            },
+            "torrent_id": torrent_id,
+            "torrent_hash": format!("hash-{title}-{episode}"),
+            "state": state,
+        }))
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nyauser_types::PullState;
+
+    use super::{
+        testing::{pull, temporary},
+        *,
+    };
+
+    #[test]
+    fn test_copy_into() {
+        for (from, to) in [
+            (DbBackend::Sled, DbBackend::Sqlite),
+            (DbBackend::Sqlite, DbBackend::Sled),
+        ] {
+            let source = temporary(from);
+            source.storage.put(Kind::Series, "Foo", b"{}").unwrap();
+            source
+                .save_pull(&pull(
+                    "Foo",
+                    Some("Foo"),
+                    1,
+                    PullState::Downloading,
+                    Some(7),
+                ))
+                .unwrap();
+            source
+                .save_pull(&pull("Foo", Some("Foo"), 2, PullState::Finished, None))
+                .unwrap();
+            let corrupt = pull("Foo", Some("Foo"), 3, PullState::Finished, None);
+            source
+                .storage
+                .save_pull_at("Foo_S01E03", &corrupt, b"not json")
+                .unwrap();
+            source.storage.put_decision("Foo", "001", b"{}").unwrap();
+            source
+                .storage
+                .set_meta(migration::SCHEMA_VERSION_KEY, "4")
+                .unwrap();
+
+            let target = temporary(to);
+            source.copy_into(&target).unwrap();
+            assert_eq!(
+                target.storage.list(Kind::Series).unwrap(),
+                source.storage.list(Kind::Series).unwrap()
+            );
+            let keys = target
+                .list_pull_entry()
+                .unwrap()
+                .into_iter()
+                .map(|x| x.key())
+                .collect::<Vec<_>>();
+            assert_eq!(keys, vec!["Foo_S01E01", "Foo_S01E02"], "{from:?} -> {to:?}");
+            assert_eq!(
+                target.storage.torrent_index().unwrap(),
+                vec![(7, "Foo_S01E01".to_string())]
+            );
+            assert_eq!(
+                target.storage.list_all_decisions().unwrap(),
+                vec![("Foo".to_string(), "001".to_string(), b"{}".to_vec())]
+            );
+            assert_eq!(target.schema_version().unwrap(), 4);
+        }
+    }
 }
@@ -2,27 +2,23 @@ use nyauser_types::Profile;
 
 use anyhow::Result;
 
-use super::Database;
+use super::{storage::Kind, Database};
 
 impl Database {
     pub fn save_profile(&self, profile: &Profile) -> Result<()> {
-        self.db.insert(
-            format!("profile-{}", profile.name),
-            serde_json::to_string(profile)?.as_bytes(),
-        )?;
-        Ok(())
+        self.storage
+            .put(Kind::Profile, &profile.name, &serde_json::to_vec(profile)?)
     }
 
     pub fn delete_profile(&self, name: &str) -> Result<()> {
-        self.db.remove(&format!("profile-{name}"))?;
-        Ok(())
+        self.storage.remove(Kind::Profile, name)
     }
 
     pub fn get_profile(&self, name: &str) -> Result<Option<Profile>> {
-        self.get_serde("profile", name)
+        self.get_serde(Kind::Profile, name)
     }
 
     pub fn list_profile(&self) -> Result<Vec<Profile>> {
-        self.list_serde("profile-")
+        self.list_serde(Kind::Profile)
     }
 }
@@ -1,73 +1,77 @@
 use nyauser_types::PullEntry;
 
-use super::Database;
+use super::{parse_all, storage::PullQuery, Database};
 use anyhow::Result;
 
 impl Database {
     /// includes an interior save
     pub fn clear_torrent_id(&self, pull: &mut PullEntry) -> Result<()> {
-        if let Some(torrent_id) = pull.torrent_id.take() {
-            self.db.remove(format!("downloading-{}", torrent_id))?;
-        }
-        self.save_pull(pull)?;
-        Ok(())
+        pull.torrent_id = None;
+        self.save_pull(pull)
     }
 
+    /// Writes the record and its torrent id index atomically,
+    /// dropping the index of a previous torrent of the entry
     pub fn save_pull(&self, pull: &PullEntry) -> Result<()> {
-        let key = pull.key();
-        self.db.insert(
-            format!("torrent-{key}"),
-            serde_json::to_string(pull)?.as_bytes(),
-        )?;
-        if let Some(torrent_id) = pull.torrent_id {
-            self.db
-                .insert(format!("downloading-{}", torrent_id), key.as_bytes())?;
-        }
-        Ok(())
+        self.storage.save_pull(pull, &serde_json::to_vec(pull)?)
     }
 
-    pub fn delete_pull(&self, mut pull: PullEntry) -> Result<()> {
-        if let Some(torrent_id) = pull.torrent_id.take() {
-            self.db.remove(format!("downloading-{}", torrent_id))?;
-        }
-        self.db.remove(format!("torrent-{}", pull.key()))?;
-        Ok(())
+    pub fn delete_pull(&self, pull: PullEntry) -> Result<()> {
+        self.storage.remove_pull(&pull.key())
     }
 }
 
 impl Database {
     pub fn exists_pull_entry(&self, key: &str) -> Result<bool> {
-        Ok(self.db.contains_key(format!("torrent-{key}"))?)
+        Ok(self.storage.get_pull(key)?.is_some())
+    }
+
+    /// true if a pull entry exists for `key` that a new release may not replace
+    pub fn blocks_new_grab(&self, key: &str) -> Result<bool> {
+        Ok(self
+            .get_pull_entry(key)?
+            .map(|x| !x.is_replaceable())
+            .unwrap_or(false))
     }
 
     pub fn get_pull_entry(&self, key: &str) -> Result<Option<PullEntry>> {
-        self.get_serde("torrent", key)
+        let Some(raw) = self.storage.get_pull(key)? else {
+            return Ok(None);
+        };
+        Ok(Some(serde_json::from_slice(&raw)?))
     }
 
     pub fn get_pull_entry_from_torrent_id(&self, id: i64) -> Result<Option<PullEntry>> {
-        let downloading_id = format!("downloading-{}", id);
-        let internal_id = match self.db.get(&downloading_id)? {
-            None => return Ok(None),
-            Some(x) => String::from_utf8(x.to_vec())?,
+        let Some(raw) = self.storage.get_pull_by_torrent_id(id)? else {
+            return Ok(None);
         };
-        self.get_pull_entry(&internal_id)
+        Ok(Some(serde_json::from_slice(&raw)?))
     }
 
     pub fn list_pull_entry_series(&self, name: &str) -> Result<Vec<PullEntry>> {
-        self.list_serde(&format!("torrent-{name}_S"))
+        self.list_pull_entry_query(&PullQuery {
+            series: Some(name),
+            ..Default::default()
+        })
     }
 
     pub fn list_pull_entry(&self) -> Result<Vec<PullEntry>> {
-        self.list_serde("torrent-")
+        self.list_pull_entry_query(&PullQuery::default())
+    }
+
+    /// filtering is pushed down to the backend where it has indexes for it
+    pub fn list_pull_entry_query(&self, query: &PullQuery) -> Result<Vec<PullEntry>> {
+        let mut out: Vec<PullEntry> = parse_all("torrent", self.storage.list_pulls(query)?);
+        out.retain(|x| query.matches(x));
+        Ok(out)
     }
 
     pub fn list_pull_entry_downloading(&self) -> Result<Vec<PullEntry>> {
         let mut out = vec![];
-        for entry in self.db.scan_prefix("downloading-") {
-            let (_, value) = entry?;
-            let key = std::str::from_utf8(&value[..])?;
+        for (_, key) in self.storage.torrent_index()? {
             let Some(pull_entry) = self.get_pull_entry(&key)? else {
-                bail!("dangling `downloading` key");
+                warn!("skipping dangling `downloading` key for '{key}', run --fsck to repair");
+                continue;
             };
             out.push(pull_entry);
         }
@@ -0,0 +1,212 @@
+use anyhow::Result;
+use nyauser_types::PullEntry;
+use sled::{
+    transaction::{ConflictableTransactionResult, TransactionError, TransactionalTree},
+    Db, Tree,
+};
+
+use super::storage::{Kind, PullQuery, Storage};
+
+/// Records live under `<kind>-<key>`, pull entries under `torrent-<key>` with a
+/// `downloading-<torrent id>` index. Decisions are in their own tree, keyed `<series>\0<seq>`.
+pub struct SledStorage {
+    db: Db,
+    decisions: Tree,
+}
+
+impl SledStorage {
+    pub fn new(db: Db) -> Result<Self> {
+        Ok(Self {
+            decisions: db.open_tree("decisions")?,
+            db,
+        })
+    }
+}
+
+fn downloading_key(torrent_id: i64) -> String {
+    format!("downloading-{torrent_id}")
+}
+
+/// keys are `<series>\0<seq>`, so one series' prefix can't match another's
+fn decision_prefix(series: &str) -> String {
+    format!("{series}\0")
+}
+
+/// removes the index for `torrent_id` only if it still points at `key`
+fn remove_index(
+    tx: &TransactionalTree,
+    torrent_id: i64,
+    key: &str,
+) -> ConflictableTransactionResult<()> {
+    let index = downloading_key(torrent_id);
+    if tx.get(&index)?.as_deref() == Some(key.as_bytes()) {
+        tx.remove(index.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn scan(tree: &Tree, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut out = vec![];
+    for entry in tree.scan_prefix(prefix) {
+        let (key, value) = entry?;
+        let key = String::from_utf8_lossy(&key[prefix.len()..]).into_owned();
+        out.push((key, value.to_vec()));
+    }
+    Ok(out)
+}
+
+impl Storage for SledStorage {
+    fn get(&self, kind: Kind, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .db
+            .get(format!("{}-{key}", kind.as_str()))?
+            .map(|x| x.to_vec()))
+    }
+
+    fn list(&self, kind: Kind) -> Result<Vec<(String, Vec<u8>)>> {
+        scan(&self.db, &format!("{}-", kind.as_str()))
+    }
+
+    fn put(&self, kind: Kind, key: &str, value: &[u8]) -> Result<()> {
+        self.db.insert(format!("{}-{key}", kind.as_str()), value)?;
+        Ok(())
+    }
+
+    fn remove(&self, kind: Kind, key: &str) -> Result<()> {
+        self.db.remove(format!("{}-{key}", kind.as_str()))?;
+        Ok(())
+    }
+
+    fn get_pull(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.db.get(format!("torrent-{key}"))?.map(|x| x.to_vec()))
+    }
+
+    fn get_pull_by_torrent_id(&self, torrent_id: i64) -> Result<Option<Vec<u8>>> {
+        let Some(key) = self.db.get(downloading_key(torrent_id))? else {
+            return Ok(None);
+        };
+        self.get_pull(std::str::from_utf8(&key)?)
+    }
+
+    fn list_pulls(&self, query: &PullQuery) -> Result<Vec<(String, Vec<u8>)>> {
+        // keys start with the series, other fields need a full scan
+        match query.series {
+            Some(series) => Ok(scan(&self.db, &format!("torrent-{series}_S"))?
+                .into_iter()
+                .map(|(key, value)| (format!("{series}_S{key}"), value))
+                .collect()),
+            None => scan(&self.db, "torrent-"),
+        }
+    }
+
+    fn save_pull(&self, pull: &PullEntry, value: &[u8]) -> Result<()> {
+        self.save_pull_at(&pull.key(), pull, value)
+    }
+
+    fn save_pull_at(&self, key: &str, pull: &PullEntry, value: &[u8]) -> Result<()> {
+        let record_key = format!("torrent-{key}");
+        self.db
+            .transaction(|tx| {
+                // drop the index of a previous torrent of this entry
+                if let Some(previous) = tx.get(&record_key)? {
+                    let previous_id = serde_json::from_slice::<PullEntry>(&previous)
+                        .ok()
+                        .and_then(|x| x.torrent_id)
+                        .filter(|x| Some(*x) != pull.torrent_id);
+                    if let Some(previous_id) = previous_id {
+                        remove_index(tx, previous_id, key)?;
+                    }
+                }
+                tx.insert(record_key.as_bytes(), value)?;
+                if let Some(torrent_id) = pull.torrent_id {
+                    tx.insert(downloading_key(torrent_id).as_bytes(), key.as_bytes())?;
+                }
+                Ok(())
+            })
+            .map_err(|e: TransactionError<()>| anyhow!("failed to save '{key}': {e:?}"))
+    }
+
+    fn remove_pull(&self, key: &str) -> Result<()> {
+        let record_key = format!("torrent-{key}");
+        self.db
+            .transaction(|tx| {
+                if let Some(previous) = tx.get(&record_key)? {
+                    let torrent_id = serde_json::from_slice::<PullEntry>(&previous)
+                        .ok()
+                        .and_then(|x| x.torrent_id);
+                    if let Some(torrent_id) = torrent_id {
+                        remove_index(tx, torrent_id, key)?;
+                    }
+                }
+                tx.remove(record_key.as_bytes())?;
+                Ok(())
+            })
+            .map_err(|e: TransactionError<()>| anyhow!("failed to delete '{key}': {e:?}"))
+    }
+
+    fn torrent_index(&self) -> Result<Vec<(i64, String)>> {
+        scan(&self.db, "downloading-")?
+            .into_iter()
+            .map(|(torrent_id, key)| Ok((torrent_id.parse()?, String::from_utf8(key)?)))
+            .collect()
+    }
+
+    fn remove_torrent_index(&self, torrent_id: i64) -> Result<()> {
+        self.db.remove(downloading_key(torrent_id))?;
+        Ok(())
+    }
+
+    fn put_decision(&self, series: &str, seq: &str, value: &[u8]) -> Result<()> {
+        self.decisions
+            .insert(format!("{}{seq}", decision_prefix(series)), value)?;
+        Ok(())
+    }
+
+    fn list_decisions(&self, series: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        scan(&self.decisions, &decision_prefix(series))
+    }
+
+    fn list_all_decisions(&self) -> Result<Vec<(String, String, Vec<u8>)>> {
+        let mut out = vec![];
+        for entry in self.decisions.iter() {
+            let (key, value) = entry?;
+            let key = String::from_utf8_lossy(&key).into_owned();
+            let (series, seq) = key.split_once('\0').unwrap_or((&key, ""));
+            out.push((series.to_string(), seq.to_string(), value.to_vec()));
+        }
+        Ok(out)
+    }
+
+    fn remove_decision(&self, series: &str, seq: &str) -> Result<()> {
+        self.decisions
+            .remove(format!("{}{seq}", decision_prefix(series)))?;
+        Ok(())
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        self.db
+            .get(format!("meta-{key}"))?
+            .map(|x| Ok(String::from_utf8(x.to_vec())?))
+            .transpose()
+    }
+
+    fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.db.insert(format!("meta-{key}"), value.as_bytes())?;
+        Ok(())
+    }
+
+    fn quarantine(&self, name: &str, value: &[u8]) -> Result<()> {
+        self.db.insert(format!("corrupt-{name}"), value)?;
+        Ok(())
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.db.is_empty() && self.decisions.is_empty())
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        self.decisions.flush()?;
+        Ok(())
+    }
+}
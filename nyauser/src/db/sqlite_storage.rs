@@ -0,0 +1,279 @@
+use std::{path::Path, sync::Mutex};
+
+use anyhow::Result;
+use nyauser_types::PullEntry;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::storage::{Kind, PullQuery, Storage};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS series (key TEXT PRIMARY KEY, data TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS profile (key TEXT PRIMARY KEY, data TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS block (key TEXT PRIMARY KEY, data TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS pull_entry (
+    key TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    series TEXT,
+    profile TEXT NOT NULL,
+    state TEXT NOT NULL,
+    torrent_hash TEXT NOT NULL,
+    torrent_id INTEGER,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS pull_entry_title ON pull_entry (title);
+CREATE INDEX IF NOT EXISTS pull_entry_series ON pull_entry (series);
+CREATE INDEX IF NOT EXISTS pull_entry_profile ON pull_entry (profile);
+CREATE INDEX IF NOT EXISTS pull_entry_state ON pull_entry (state);
+CREATE INDEX IF NOT EXISTS pull_entry_torrent_hash ON pull_entry (torrent_hash);
+CREATE INDEX IF NOT EXISTS pull_entry_torrent_id ON pull_entry (torrent_id);
+CREATE TABLE IF NOT EXISTS decision (
+    series TEXT NOT NULL,
+    seq TEXT NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (series, seq)
+);
+CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS corrupt (key TEXT PRIMARY KEY, data BLOB NOT NULL);
+";
+
+/// Pull entries are stored with their indexed fields as columns next to the JSON record.
+/// The torrent id index is a column, so it can't diverge from the record.
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+}
+
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).into_owned()
+}
+
+impl Storage for SqliteStorage {
+    fn get(&self, kind: Kind, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .conn()
+            .query_row(
+                &format!("SELECT data FROM {} WHERE key = ?", kind.as_str()),
+                [key],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .map(String::into_bytes))
+    }
+
+    fn list(&self, kind: Kind) -> Result<Vec<(String, Vec<u8>)>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT key, data FROM {} ORDER BY key",
+            kind.as_str()
+        ))?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get(0)?, row.get::<_, String>(1)?.into_bytes()))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn put(&self, kind: Kind, key: &str, value: &[u8]) -> Result<()> {
+        self.conn().execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} (key, data) VALUES (?, ?)",
+                kind.as_str()
+            ),
+            params![key, text(value)],
+        )?;
+        Ok(())
+    }
+
+    fn remove(&self, kind: Kind, key: &str) -> Result<()> {
+        self.conn().execute(
+            &format!("DELETE FROM {} WHERE key = ?", kind.as_str()),
+            [key],
+        )?;
+        Ok(())
+    }
+
+    fn get_pull(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .conn()
+            .query_row("SELECT data FROM pull_entry WHERE key = ?", [key], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()?
+            .map(String::into_bytes))
+    }
+
+    fn get_pull_by_torrent_id(&self, torrent_id: i64) -> Result<Option<Vec<u8>>> {
+        // the most recently saved entry wins if a sink reused an id
+        Ok(self
+            .conn()
+            .query_row(
+                "SELECT data FROM pull_entry WHERE torrent_id = ? ORDER BY rowid DESC LIMIT 1",
+                [torrent_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .map(String::into_bytes))
+    }
+
+    fn list_pulls(&self, query: &PullQuery) -> Result<Vec<(String, Vec<u8>)>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT key, data FROM pull_entry WHERE
+                (?1 IS NULL OR title = ?1) AND
+                (?2 IS NULL OR series = ?2) AND
+                (?3 IS NULL OR profile = ?3) AND
+                (?4 IS NULL OR state = ?4) AND
+                (?5 IS NULL OR torrent_hash = lower(?5))
+            ORDER BY key",
+        )?;
+        let rows = statement.query_map(
+            params![
+                query.title,
+                query.series,
+                query.profile,
+                query.state.map(|x| x.as_str()),
+                query.torrent_hash,
+            ],
+            |row| Ok((row.get(0)?, row.get::<_, String>(1)?.into_bytes())),
+        )?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn save_pull(&self, pull: &PullEntry, value: &[u8]) -> Result<()> {
+        self.save_pull_at(&pull.key(), pull, value)
+    }
+
+    fn save_pull_at(&self, key: &str, pull: &PullEntry, value: &[u8]) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO pull_entry
+                (key, title, series, profile, state, torrent_hash, torrent_id, data)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                key,
+                pull.result.parsed.title,
+                pull.result.series,
+                pull.result.profile,
+                pull.state.as_str(),
+                pull.torrent_hash.to_ascii_lowercase(),
+                pull.torrent_id,
+                text(value),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn remove_pull(&self, key: &str) -> Result<()> {
+        self.conn()
+            .execute("DELETE FROM pull_entry WHERE key = ?", [key])?;
+        Ok(())
+    }
+
+    fn torrent_index(&self) -> Result<Vec<(i64, String)>> {
+        let conn = self.conn();
+        let mut statement =
+            conn.prepare("SELECT torrent_id, key FROM pull_entry WHERE torrent_id IS NOT NULL")?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn remove_torrent_index(&self, _torrent_id: i64) -> Result<()> {
+        // the index is a column of the record, there is nothing separate to remove
+        Ok(())
+    }
+
+    fn put_decision(&self, series: &str, seq: &str, value: &[u8]) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO decision (series, seq, data) VALUES (?, ?, ?)",
+            params![series, seq, text(value)],
+        )?;
+        Ok(())
+    }
+
+    fn list_decisions(&self, series: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let conn = self.conn();
+        let mut statement =
+            conn.prepare("SELECT seq, data FROM decision WHERE series = ? ORDER BY seq")?;
+        let rows = statement.query_map([series], |row| {
+            Ok((row.get(0)?, row.get::<_, String>(1)?.into_bytes()))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn list_all_decisions(&self) -> Result<Vec<(String, String, Vec<u8>)>> {
+        let conn = self.conn();
+        let mut statement =
+            conn.prepare("SELECT series, seq, data FROM decision ORDER BY series, seq")?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get::<_, String>(2)?.into_bytes(),
+            ))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn remove_decision(&self, series: &str, seq: &str) -> Result<()> {
+        self.conn().execute(
+            "DELETE FROM decision WHERE series = ? AND seq = ?",
+            [series, seq],
+        )?;
+        Ok(())
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn()
+            .query_row("SELECT value FROM meta WHERE key = ?", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?, ?)",
+            [key, value],
+        )?;
+        Ok(())
+    }
+
+    fn quarantine(&self, name: &str, value: &[u8]) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO corrupt (key, data) VALUES (?, ?)",
+            params![name, value],
+        )?;
+        Ok(())
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        let count: i64 = self.conn().query_row(
+            "SELECT (SELECT count(*) FROM series) + (SELECT count(*) FROM profile)
+                + (SELECT count(*) FROM block) + (SELECT count(*) FROM pull_entry)
+                + (SELECT count(*) FROM decision) + (SELECT count(*) FROM meta)",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count == 0)
+    }
+
+    fn flush(&self) -> Result<()> {
+        // every statement is its own committed transaction
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+use anyhow::Result;
+use nyauser_types::{PullEntry, PullState};
+
+/// records stored by name, without indexes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Series,
+    Profile,
+    Block,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 3] = [Kind::Series, Kind::Profile, Kind::Block];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Series => "series",
+            Kind::Profile => "profile",
+            Kind::Block => "block",
+        }
+    }
+}
+
+/// Narrows a pull entry listing. Backends may return extra entries, `Database` filters them out.
+#[derive(Default, Clone, Debug)]
+pub struct PullQuery<'a> {
+    /// parsed release title
+    pub title: Option<&'a str>,
+    /// owning series, which is also the start of the key
+    pub series: Option<&'a str>,
+    pub profile: Option<&'a str>,
+    pub state: Option<PullState>,
+    pub torrent_hash: Option<&'a str>,
+}
+
+impl<'a> PullQuery<'a> {
+    pub fn matches(&self, pull: &PullEntry) -> bool {
+        self.title.map_or(true, |x| pull.result.parsed.title == x)
+            && self
+                .series
+                .map_or(true, |x| pull.result.series.as_deref() == Some(x))
+            && self.profile.map_or(true, |x| pull.result.profile == x)
+            && self.state.map_or(true, |x| pull.state == x)
+            && self
+                .torrent_hash
+                .map_or(true, |x| pull.torrent_hash.eq_ignore_ascii_case(x))
+    }
+}
+
+/// Where `Database` keeps its records. Values are JSON, and are only deserialized by `Database`
+/// so corrupt records can be reported individually.
+pub trait Storage: Send + Sync {
+    fn get(&self, kind: Kind, key: &str) -> Result<Option<Vec<u8>>>;
+    fn list(&self, kind: Kind) -> Result<Vec<(String, Vec<u8>)>>;
+    fn put(&self, kind: Kind, key: &str, value: &[u8]) -> Result<()>;
+    fn remove(&self, kind: Kind, key: &str) -> Result<()>;
+
+    fn get_pull(&self, key: &str) -> Result<Option<Vec<u8>>>;
+    fn get_pull_by_torrent_id(&self, torrent_id: i64) -> Result<Option<Vec<u8>>>;
+    fn list_pulls(&self, query: &PullQuery) -> Result<Vec<(String, Vec<u8>)>>;
+    /// writes the record and its torrent id index atomically
+    fn save_pull(&self, pull: &PullEntry, value: &[u8]) -> Result<()>;
+    /// `save_pull` under `key` rather than the entry's current key, as older versions keyed it
+    fn save_pull_at(&self, key: &str, pull: &PullEntry, value: &[u8]) -> Result<()>;
+    /// removes the record and its torrent id index atomically
+    fn remove_pull(&self, key: &str) -> Result<()>;
+    /// torrent id -> pull entry key
+    fn torrent_index(&self) -> Result<Vec<(i64, String)>>;
+    fn remove_torrent_index(&self, torrent_id: i64) -> Result<()>;
+
+    /// `seq` orders a series' decisions
+    fn put_decision(&self, series: &str, seq: &str, value: &[u8]) -> Result<()>;
+    /// (seq, value), oldest first
+    fn list_decisions(&self, series: &str) -> Result<Vec<(String, Vec<u8>)>>;
+    /// (series, seq, value) of every series
+    fn list_all_decisions(&self) -> Result<Vec<(String, String, Vec<u8>)>>;
+    fn remove_decision(&self, series: &str, seq: &str) -> Result<()>;
+
+    fn get_meta(&self, key: &str) -> Result<Option<String>>;
+    fn set_meta(&self, key: &str, value: &str) -> Result<()>;
+
+    /// keeps a corrupt record out of the way of listings
+    fn quarantine(&self, name: &str, value: &[u8]) -> Result<()>;
+
+    fn is_empty(&self) -> Result<bool>;
+    fn flush(&self) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use nyauser_types::PullState;
+
+    use super::*;
+    use crate::db::{
+        testing::{pull, temporary},
+        DbBackend,
+    };
+
+    const BACKENDS: [DbBackend; 2] = [DbBackend::Sled, DbBackend::Sqlite];
+
+    #[test]
+    fn test_round_trip() {
+        for backend in BACKENDS {
+            let db = temporary(backend);
+            let storage = &db.storage;
+            assert!(storage.is_empty().unwrap(), "{backend:?}");
+
+            storage.put(Kind::Series, "Foo", b"{}").unwrap();
+            assert_eq!(
+                storage.get(Kind::Series, "Foo").unwrap(),
+                Some(b"{}".to_vec())
+            );
+            assert_eq!(storage.get(Kind::Profile, "Foo").unwrap(), None);
+            assert_eq!(
+                storage.list(Kind::Series).unwrap(),
+                vec![("Foo".to_string(), b"{}".to_vec())]
+            );
+            storage.remove(Kind::Series, "Foo").unwrap();
+            assert!(storage.list(Kind::Series).unwrap().is_empty());
+
+            let mut entry = pull("Foo", Some("Foo"), 1, PullState::Downloading, Some(7));
+            db.save_pull(&entry).unwrap();
+            assert_eq!(
+                db.get_pull_entry_from_torrent_id(7).unwrap().unwrap().key(),
+                "Foo_S01E01"
+            );
+            // a new torrent replaces the index of the previous one
+            entry.torrent_id = Some(8);
+            db.save_pull(&entry).unwrap();
+            assert_eq!(
+                storage.torrent_index().unwrap(),
+                vec![(8, "Foo_S01E01".to_string())],
+                "{backend:?}"
+            );
+            assert!(db.get_pull_entry_from_torrent_id(7).unwrap().is_none());
+            db.clear_torrent_id(&mut entry).unwrap();
+            assert!(storage.torrent_index().unwrap().is_empty());
+            entry.torrent_id = Some(9);
+            db.save_pull(&entry).unwrap();
+            storage.remove_pull("Foo_S01E01").unwrap();
+            assert!(storage.get_pull("Foo_S01E01").unwrap().is_none());
+            assert!(storage.torrent_index().unwrap().is_empty(), "{backend:?}");
+
+            storage.put_decision("Foo", "002", b"2").unwrap();
+            storage.put_decision("Foo", "001", b"1").unwrap();
+            storage.put_decision("Bar", "001", b"3").unwrap();
+            assert_eq!(
+                storage.list_decisions("Foo").unwrap(),
+                vec![
+                    ("001".to_string(), b"1".to_vec()),
+                    ("002".to_string(), b"2".to_vec())
+                ]
+            );
+            assert_eq!(storage.list_all_decisions().unwrap().len(), 3);
+            storage.remove_decision("Foo", "001").unwrap();
+            assert_eq!(storage.list_decisions("Foo").unwrap().len(), 1);
+
+            assert_eq!(storage.get_meta("schema_version").unwrap(), None);
+            storage.set_meta("schema_version", "4").unwrap();
+            assert_eq!(
+                storage.get_meta("schema_version").unwrap().as_deref(),
+                Some("4")
+            );
+            assert!(!storage.is_empty().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_list_pulls_query() {
+        for backend in BACKENDS {
+            let db = temporary(backend);
+            let mut other_profile = pull("Bar", Some("Bar"), 1, PullState::Finished, None);
+            other_profile.result.profile = "other".to_string();
+            for entry in [
+                pull("Foo", Some("Foo"), 1, PullState::Downloading, Some(1)),
+                pull("Foo", Some("Foo"), 2, PullState::Finished, None),
+                pull("FOO!", Some("Foo"), 3, PullState::Finished, None),
+                other_profile,
+            ] {
+                db.save_pull(&entry).unwrap();
+            }
+            let keys = |query: PullQuery| {
+                let mut out = db
+                    .list_pull_entry_query(&query)
+                    .unwrap()
+                    .into_iter()
+                    .map(|x| x.key())
+                    .collect::<Vec<_>>();
+                out.sort();
+                out
+            };
+            assert_eq!(keys(PullQuery::default()).len(), 4, "{backend:?}");
+            assert_eq!(
+                keys(PullQuery {
+                    series: Some("Foo"),
+                    ..Default::default()
+                }),
+                vec!["Foo_S01E01", "Foo_S01E02", "Foo_S01E03"],
+                "{backend:?}"
+            );
+            assert_eq!(
+                keys(PullQuery {
+                    title: Some("FOO!"),
+                    ..Default::default()
+                }),
+                vec!["Foo_S01E03"]
+            );
+            assert_eq!(
+                keys(PullQuery {
+                    profile: Some("other"),
+                    ..Default::default()
+                }),
+                vec!["Bar_S01E01"]
+            );
+            assert_eq!(
+                keys(PullQuery {
+                    series: Some("Foo"),
+                    state: Some(PullState::Finished),
+                    ..Default::default()
+                }),
+                vec!["Foo_S01E02", "Foo_S01E03"]
+            );
+            assert_eq!(
+                keys(PullQuery {
+                    torrent_hash: Some("HASH-FOO-1"),
+                    ..Default::default()
+                }),
+                vec!["Foo_S01E01"],
+                "{backend:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_pull_query_matches() {
+        let entry = pull("Foo", Some("Foo"), 1, PullState::Downloading, None);
+        assert!(PullQuery::default().matches(&entry));
+        assert!(PullQuery {
+            title: Some("Foo"),
+            series: Some("Foo"),
+            profile: Some("group"),
+            state: Some(PullState::Downloading),
+            torrent_hash: Some("HASH-FOO-1"),
+        }
+        .matches(&entry));
+        let misses = [
+            PullQuery {
+                title: Some("foo"),
+                ..Default::default()
+            },
+            PullQuery {
+                series: Some("Bar"),
+                ..Default::default()
+            },
+            PullQuery {
+                profile: Some("other"),
+                ..Default::default()
+            },
+            PullQuery {
+                state: Some(PullState::Finished),
+                ..Default::default()
+            },
+            PullQuery {
+                torrent_hash: Some("hash-foo-2"),
+                ..Default::default()
+            },
+        ];
+        for query in misses {
+            assert!(!query.matches(&entry), "{query:?}");
+        }
+        // entries from before the series was recorded only match by title
+        let legacy = pull("Foo", None, 1, PullState::Downloading, None);
+        assert!(!PullQuery {
+            series: Some("Foo"),
+            ..Default::default()
+        }
+        .matches(&legacy));
+    }
+}
@@ -1,17 +1,27 @@
-use std::sync::Arc;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use clap::Parser;
 use config::CONFIG;
 use log::LevelFilter;
+use nyauser_types::{Backup, RestoreMode};
 use search::Searcher;
 use sink::{Sink, TransmissionClient};
 use source::{NyaaClient, Source};
 
-use crate::{api::AppState, db::Database, sink::SinkConfig, source::SourceConfig};
+use crate::{
+    api::AppState,
+    db::{Database, DbBackend},
+    sink::SinkConfig,
+    source::SourceConfig,
+};
 
 mod api;
 mod config;
 mod db;
+mod relocate;
 mod search;
 mod sink;
 mod source;
@@ -32,11 +42,46 @@ struct Args {
     #[clap(long)]
     wipe_nonexistant: bool,
 
+    /// Applies the configured file ownership and modes to the existing library and exits
+    #[clap(long)]
+    fix_permissions: bool,
+
+    /// Exports profiles, series, pulls and the blocklist to a JSON or YAML (by extension) file and exits
+    #[clap(long, value_name = "FILE")]
+    export: Option<PathBuf>,
+
+    /// Imports an export made by `--export` and exits
+    #[clap(long, value_name = "FILE")]
+    import: Option<PathBuf>,
+
+    /// With `--import`, removes all existing records first instead of merging
+    #[clap(long, requires = "import")]
+    replace: bool,
+
+    /// Checks the database for inconsistent indexes, orphaned and corrupt records, and exits
+    #[clap(long)]
+    fsck: bool,
+
+    /// With `--fsck`, repairs what it finds
+    #[clap(long, requires = "fsck")]
+    repair: bool,
+
+    /// Copies the sled database to a new SQLite database at FILE and exits
+    #[clap(long, value_name = "FILE")]
+    migrate_to_sqlite: Option<PathBuf>,
+
     /// Increases log level
     #[clap(short, long)]
     verbose: bool,
 }
 
+fn is_yaml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|x| x.to_str()),
+        Some("yml" | "yaml")
+    )
+}
+
 #[macro_use]
 extern crate anyhow;
 
@@ -58,21 +103,81 @@ async fn main() {
         )
         .init();
 
-    let db = sled::open(&*CONFIG.db_file).expect("sled failed to init db");
+    let db = Database::open(CONFIG.db_backend, Path::new(&CONFIG.db_file))
+        .expect("failed to open database");
     if args.dump {
         println!("dumping");
-        for (key, value) in db.iter().map(|x| x.unwrap()) {
-            let key = std::str::from_utf8(&key[..]).unwrap();
-            let value = std::str::from_utf8(&value[..]).unwrap();
+        for (key, value) in db.dump().expect("failed to dump database") {
             println!("{} = {}", key, value);
         }
         return;
     }
-    let db = Arc::new(Database::new(db));
+    db.migrate(Path::new(&CONFIG.db_file))
+        .expect("failed to migrate database");
+    if let Some(path) = &args.migrate_to_sqlite {
+        if CONFIG.db_backend == DbBackend::Sqlite {
+            error!("`db_backend` is already sqlite");
+            std::process::exit(1);
+        }
+        if path.exists() {
+            error!(
+                "{} already exists, refusing to merge into it",
+                path.display()
+            );
+            std::process::exit(1);
+        }
+        let target =
+            Database::open(DbBackend::Sqlite, path).expect("failed to open sqlite database");
+        db.copy_into(&target).expect("failed to copy database");
+        info!(
+            "copied database to {}, set `db_file` to it and `db_backend: sqlite` to use it",
+            path.display()
+        );
+        return;
+    }
+    let db = Arc::new(db);
     if args.wipe_nonexistant {
         search::wipe_nonexistant(&db).expect("wipe_nonexistant failed");
         return;
     }
+    if args.fsck {
+        let findings = db.fsck(args.repair).expect("fsck failed");
+        info!("fsck found {} issues", findings.len());
+        return;
+    }
+    if let Some(path) = &args.export {
+        let backup = db.export().expect("export failed");
+        let raw = if is_yaml(path) {
+            serde_yaml::to_string(&backup).expect("failed to serialize export")
+        } else {
+            serde_json::to_string_pretty(&backup).expect("failed to serialize export")
+        };
+        std::fs::write(path, raw).expect("failed to write export");
+        info!("exported to {}", path.display());
+        return;
+    }
+    if let Some(path) = &args.import {
+        let raw = std::fs::read_to_string(path).expect("failed to read import");
+        let backup: Backup = if is_yaml(path) {
+            serde_yaml::from_str(&raw).expect("failed to parse import")
+        } else {
+            serde_json::from_str(&raw).expect("failed to parse import")
+        };
+        let mode = if args.replace {
+            RestoreMode::Replace
+        } else {
+            RestoreMode::Merge
+        };
+        let report = db.restore(backup, mode).expect("import failed");
+        info!("imported {report:?}");
+        return;
+    }
+    if args.fix_permissions {
+        let count = search::fix_permissions(&db, &CONFIG.search.permissions)
+            .expect("fix_permissions failed");
+        info!("fixed permissions of {count} files and directories");
+        return;
+    }
 
     let Some(source_config) = CONFIG.sources.get(&CONFIG.search.source) else {
         error!("invalid source {}, not found", CONFIG.search.source);
@@ -86,16 +191,19 @@ async fn main() {
     let sink: Box<dyn Sink + Send + Sync> = match sink_config {
         SinkConfig::Transmission(config) => Box::new(TransmissionClient::new(config.clone())),
     };
-    let source: Box<dyn Source + Send + Sync> = match source_config {
-        SourceConfig::Nyaa(config) => Box::new(NyaaClient::new(config.clone())),
+    let source: Arc<dyn Source + Send + Sync> = match source_config {
+        SourceConfig::Nyaa(config) => Arc::new(NyaaClient::new(config.clone())),
     };
-    let mut searcher = Searcher::new(db.clone(), source, sink, CONFIG.search.clone())
-        .expect("failed to init searcher");
+    let searcher = Arc::new(
+        Searcher::new(db.clone(), source, sink, CONFIG.search.clone())
+            .expect("failed to init searcher"),
+    );
     if args.clean {
         searcher.clean().await.expect("clean failed");
         return;
     }
     for profile in &CONFIG.profiles {
+        profile.validate().expect("Invalid profile");
         db.save_profile(profile).expect("Failed to load profile");
     }
     for series in &CONFIG.series {
@@ -106,6 +214,7 @@ async fn main() {
         database: db,
         scan: searcher.scan().clone(),
         search: searcher.search().clone(),
+        searcher: searcher.clone(),
     });
 
     info!("running searcher");
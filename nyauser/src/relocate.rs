@@ -0,0 +1,419 @@
+use std::{
+    ffi::CString,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use chrono::Utc;
+use nyauser_types::ConflictOutcome;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+/// name of the recycle directory created in relocate directories if `RecycleConfig::dir` is unset
+pub const RECYCLE_DIR: &str = ".recycle";
+
+/// what to do when a relocated file already exists at its destination
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// keep the existing file
+    Skip,
+    /// recycle the existing file
+    #[default]
+    Overwrite,
+    /// store the new file with a ` (N)` suffix
+    KeepBoth,
+    /// recycle the existing file only if the new one is a higher version, or the same version and larger
+    ReplaceIfBetter,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RecycleConfig {
+    /// where replaced files are moved to, defaulting to `.recycle` in each relocate directory
+    pub dir: Option<String>,
+    /// recycled files older than this are deleted
+    pub retention_days: u64,
+}
+
+impl Default for RecycleConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            retention_days: 30,
+        }
+    }
+}
+
+/// ownership and modes applied to relocated files and the directories created for them
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PermissionsConfig {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// octal string, i.e. "0644"
+    #[serde(deserialize_with = "deserialize_mode")]
+    pub file_mode: Option<u32>,
+    /// octal string, i.e. "0755"
+    #[serde(deserialize_with = "deserialize_mode")]
+    pub dir_mode: Option<u32>,
+}
+
+fn deserialize_mode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    let Some(mode) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+        .map(Some)
+        .map_err(|_| serde::de::Error::custom(format!("invalid octal mode '{mode}'")))
+}
+
+/// `std::os::unix::fs::chown`, which needs a newer Rust than the Docker image builds with.
+/// An unset id is left unchanged.
+fn chown(path: &Path, uid: Option<u32>, gid: Option<u32>) -> std::io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let uid = uid.unwrap_or(libc::uid_t::MAX);
+    let gid = gid.unwrap_or(libc::gid_t::MAX);
+    // SAFETY: `path` is a valid NUL terminated string that outlives the call
+    if unsafe { libc::chown(path.as_ptr(), uid, gid) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl PermissionsConfig {
+    pub fn is_empty(&self) -> bool {
+        self.uid.is_none()
+            && self.gid.is_none()
+            && self.file_mode.is_none()
+            && self.dir_mode.is_none()
+    }
+
+    /// Applies ownership and the file or directory mode to `path`
+    pub fn apply(&self, path: &Path) -> Result<()> {
+        if self.uid.is_some() || self.gid.is_some() {
+            chown(path, self.uid, self.gid)
+                .map_err(|e| anyhow!("failed to chown {}: {e}", path.display()))?;
+        }
+        let mode = if path.is_dir() {
+            self.dir_mode
+        } else {
+            self.file_mode
+        };
+        if let Some(mode) = mode {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+                .map_err(|e| anyhow!("failed to chmod {}: {e}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// `create_dir_all`, applying permissions to each directory it creates
+    pub async fn create_dir_all(&self, dir: &Path) -> Result<()> {
+        let created: Vec<&Path> = dir.ancestors().take_while(|x| !x.exists()).collect();
+        tokio::fs::create_dir_all(dir).await?;
+        for dir in created.into_iter().rev() {
+            self.apply(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Applies permissions to `root` and everything below it, returning how many paths were visited.
+    /// Symlinks below `root` are skipped, chmod and chown would change whatever they point to.
+    pub fn apply_recursive(&self, root: &Path) -> Result<usize> {
+        self.apply(root)?;
+        let mut count = 1;
+        if root.is_dir() {
+            for entry in std::fs::read_dir(root)? {
+                let entry = entry?;
+                // file_type doesn't follow symlinks
+                if entry.file_type()?.is_symlink() {
+                    continue;
+                }
+                count += self.apply_recursive(&entry.path())?;
+            }
+        }
+        Ok(count)
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref VERSION: Regex = Regex::new(r"(?i)\dv(\d{1,2})\b").unwrap();
+}
+
+/// the `v2` in `Show - 01v2`, 1 if there is none
+fn release_version(name: &str) -> u32 {
+    VERSION
+        .captures(name)
+        .and_then(|x| x[1].parse().ok())
+        .unwrap_or(1)
+}
+
+/// Moves `from` to `to`, resolving an existing file at `to` per `policy`.
+/// `title` is the release title, used to find the new file's version.
+pub async fn relocate_file(
+    from: &Path,
+    to: &Path,
+    title: &str,
+    policy: ConflictPolicy,
+    recycle_dir: &Path,
+    permissions: &PermissionsConfig,
+) -> Result<Option<ConflictOutcome>> {
+    if let Some(parent) = to.parent() {
+        permissions.create_dir_all(parent).await?;
+    }
+    if !to.exists() {
+        tokio::fs::rename(from, to).await?;
+        permissions.apply(to)?;
+        return Ok(None);
+    }
+    let outcome = match policy {
+        ConflictPolicy::Skip => ConflictOutcome::Skipped,
+        ConflictPolicy::Overwrite => ConflictOutcome::Overwritten {
+            recycled: recycle(to, recycle_dir, permissions).await?,
+        },
+        ConflictPolicy::KeepBoth => {
+            let renamed = free_name(to);
+            tokio::fs::rename(from, &renamed).await?;
+            permissions.apply(&renamed)?;
+            return Ok(Some(ConflictOutcome::KeptBoth {
+                renamed: renamed
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+            }));
+        }
+        ConflictPolicy::ReplaceIfBetter => {
+            let name = |path: &Path| {
+                path.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            let new_version = release_version(&name(from)).max(release_version(title));
+            let old_version = release_version(&name(to));
+            let new_size = tokio::fs::metadata(from).await?.len();
+            let old_size = tokio::fs::metadata(to).await?.len();
+            if (new_version, new_size) > (old_version, old_size) {
+                ConflictOutcome::Replaced {
+                    recycled: recycle(to, recycle_dir, permissions).await?,
+                }
+            } else {
+                ConflictOutcome::KeptExisting
+            }
+        }
+    };
+    if matches!(
+        outcome,
+        ConflictOutcome::Overwritten { .. } | ConflictOutcome::Replaced { .. }
+    ) {
+        tokio::fs::rename(from, to).await?;
+        permissions.apply(to)?;
+    }
+    Ok(Some(outcome))
+}
+
+/// `name (N).ext` for the first N that doesn't exist
+fn free_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|x| format!(".{}", x.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{stem} ({n}){ext}")))
+        .find(|x| !x.exists())
+        .unwrap()
+}
+
+/// Moves `path` into `recycle_dir`, prefixed with the current time for `purge_recycle`
+async fn recycle(
+    path: &Path,
+    recycle_dir: &Path,
+    permissions: &PermissionsConfig,
+) -> Result<String> {
+    permissions.create_dir_all(recycle_dir).await?;
+    let recycled = recycle_dir.join(format!(
+        "{}_{}",
+        Utc::now().timestamp(),
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    tokio::fs::rename(path, &recycled).await?;
+    info!("recycled {} to {}", path.display(), recycled.display());
+    Ok(recycled.display().to_string())
+}
+
+/// Deletes recycled files older than `retention_days`
+pub async fn purge_recycle(recycle_dir: &Path, retention_days: u64) -> Result<()> {
+    if !recycle_dir.exists() {
+        return Ok(());
+    }
+    let cutoff = Utc::now().timestamp() - (retention_days * 24 * 60 * 60) as i64;
+    let mut entries = tokio::fs::read_dir(recycle_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let recycled_at = name
+            .to_string_lossy()
+            .split_once('_')
+            .and_then(|(at, _)| at.parse::<i64>().ok());
+        if matches!(recycled_at, Some(at) if at < cutoff) {
+            info!("purging recycled {}", entry.path().display());
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// a downloaded file to relocate onto an existing one, in a temporary directory
+    struct Conflict {
+        _dir: tempfile::TempDir,
+        from: PathBuf,
+        to: PathBuf,
+        recycle_dir: PathBuf,
+    }
+
+    impl Conflict {
+        /// `name` with `content` downloaded, and `Show - 01.mkv` already in the library
+        fn new(name: &str, content: &str) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let from = dir.path().join("download").join(name);
+            let to = dir.path().join("library").join("Show - 01.mkv");
+            fs::create_dir_all(from.parent().unwrap()).unwrap();
+            fs::create_dir_all(to.parent().unwrap()).unwrap();
+            fs::write(&from, content).unwrap();
+            fs::write(&to, "existing").unwrap();
+            Self {
+                recycle_dir: dir.path().join("library").join(RECYCLE_DIR),
+                _dir: dir,
+                from,
+                to,
+            }
+        }
+
+        async fn relocate(&self, policy: ConflictPolicy) -> Option<ConflictOutcome> {
+            relocate_file(
+                &self.from,
+                &self.to,
+                "[Group] Show - 01",
+                policy,
+                &self.recycle_dir,
+                &PermissionsConfig::default(),
+            )
+            .await
+            .unwrap()
+        }
+
+        fn library(&self) -> String {
+            fs::read_to_string(&self.to).unwrap()
+        }
+
+        /// contents of the only recycled file
+        fn recycled(&self, outcome: &ConflictOutcome) -> String {
+            let (ConflictOutcome::Overwritten { recycled }
+            | ConflictOutcome::Replaced { recycled }) = outcome
+            else {
+                panic!("nothing recycled: {outcome:?}");
+            };
+            assert_eq!(fs::read_dir(&self.recycle_dir).unwrap().count(), 1);
+            fs::read_to_string(recycled).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relocate_without_conflict() {
+        let conflict = Conflict::new("Show - 01.mkv", "new");
+        fs::remove_file(&conflict.to).unwrap();
+        assert_eq!(conflict.relocate(ConflictPolicy::Skip).await, None);
+        assert_eq!(conflict.library(), "new");
+        assert!(!conflict.from.exists());
+    }
+
+    #[tokio::test]
+    async fn test_conflict_skip() {
+        let conflict = Conflict::new("Show - 01.mkv", "new");
+        let outcome = conflict.relocate(ConflictPolicy::Skip).await;
+        assert_eq!(outcome, Some(ConflictOutcome::Skipped));
+        assert_eq!(conflict.library(), "existing");
+        assert!(conflict.from.exists());
+        assert!(!conflict.recycle_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_conflict_overwrite() {
+        let conflict = Conflict::new("Show - 01.mkv", "new");
+        let outcome = conflict.relocate(ConflictPolicy::Overwrite).await.unwrap();
+        assert_eq!(conflict.library(), "new");
+        assert_eq!(conflict.recycled(&outcome), "existing");
+        assert!(matches!(outcome, ConflictOutcome::Overwritten { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_conflict_keep_both() {
+        let conflict = Conflict::new("Show - 01.mkv", "new");
+        fs::write(conflict.to.with_file_name("Show - 01 (1).mkv"), "older").unwrap();
+        let outcome = conflict.relocate(ConflictPolicy::KeepBoth).await;
+        assert_eq!(
+            outcome,
+            Some(ConflictOutcome::KeptBoth {
+                renamed: "Show - 01 (2).mkv".to_string()
+            })
+        );
+        assert_eq!(conflict.library(), "existing");
+        let renamed = conflict.to.with_file_name("Show - 01 (2).mkv");
+        assert_eq!(fs::read_to_string(renamed).unwrap(), "new");
+    }
+
+    #[tokio::test]
+    async fn test_conflict_replace_if_better() {
+        // a higher version wins even if smaller
+        let conflict = Conflict::new("Show - 01v2.mkv", "v2");
+        let outcome = conflict
+            .relocate(ConflictPolicy::ReplaceIfBetter)
+            .await
+            .unwrap();
+        assert_eq!(conflict.library(), "v2");
+        assert_eq!(conflict.recycled(&outcome), "existing");
+
+        // the same version only wins if larger
+        let conflict = Conflict::new("Show - 01.mkv", "new");
+        let outcome = conflict.relocate(ConflictPolicy::ReplaceIfBetter).await;
+        assert_eq!(outcome, Some(ConflictOutcome::KeptExisting));
+        assert_eq!(conflict.library(), "existing");
+        assert!(conflict.from.exists());
+
+        let conflict = Conflict::new("Show - 01.mkv", "larger than existing");
+        let outcome = conflict
+            .relocate(ConflictPolicy::ReplaceIfBetter)
+            .await
+            .unwrap();
+        assert_eq!(conflict.library(), "larger than existing");
+        assert!(matches!(outcome, ConflictOutcome::Replaced { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_purge_recycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("1_Show - 01.mkv");
+        let recent = dir
+            .path()
+            .join(format!("{}_Show - 02.mkv", Utc::now().timestamp()));
+        let unprefixed = dir.path().join("Show - 03.mkv");
+        for path in [&old, &recent, &unprefixed] {
+            fs::write(path, "").unwrap();
+        }
+        purge_recycle(dir.path(), 30).await.unwrap();
+        assert!(!old.exists());
+        assert!(recent.exists());
+        assert!(unprefixed.exists());
+        purge_recycle(&dir.path().join("missing"), 30)
+            .await
+            .unwrap();
+    }
+}
@@ -1,18 +1,75 @@
-use std::{path::Path, sync::Arc, time::Duration};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    io::Read,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
-use nyauser_types::{ParsedSearchResult, PullEntry, PullState};
+use nyauser_types::{
+    rank_key, BlockEntry, BlockRule, ConflictOutcome, Decision, DecisionRecord, FileConflict,
+    LibraryFile, LibraryImportReport, LibraryImportRequest, LibraryRescanReport,
+    LibrarySkippedSeries, MissingPolicy, ParsedSearchResult, Profile, PullEntry, PullState,
+    RejectReason, RescannedEntry, RetryState, SearchCandidate, SearchResult, Series,
+};
 use serde::Deserialize;
-use tokio::{select, sync::Notify};
+use tokio::{
+    select,
+    sync::{Mutex, Notify},
+};
 
-use crate::{db::Database, sink::Sink, source::Source};
+use crate::{
+    db::Database,
+    relocate::{
+        purge_recycle, relocate_file, ConflictPolicy, PermissionsConfig, RecycleConfig, RECYCLE_DIR,
+    },
+    sink::{FinishedTorrent, Sink, TorrentStatus},
+    source::Source,
+};
 
 fn default_source_sink() -> String {
     "default".to_string()
 }
 
+fn default_decision_log_size() -> usize {
+    500
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueOrder {
+    /// latest season & episode first
+    #[default]
+    NewestFirst,
+    OldestFirst,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// pushes attempted before giving up
+    pub max_attempts: u32,
+    /// wait after the first failure, doubled after each further failure
+    pub initial_backoff_seconds: u64,
+    pub max_backoff_seconds: u64,
+    /// how often to check for retries that are due
+    pub check_seconds: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff_seconds: 60,
+            max_backoff_seconds: 60 * 60,
+            check_seconds: 60,
+        }
+    }
+}
+
 #[derive(Deserialize, Clone)]
 pub struct SearchConfig {
     /// how many days old can a torrent be to be considered
@@ -36,11 +93,37 @@ pub struct SearchConfig {
     #[serde(default)]
     pub path_patch: IndexMap<String, String>,
     pub relocate: Option<String>,
+    /// maximum torrents in the sink at once, further grabs are queued
+    /// can be limited further per series by `Series::max_active_downloads`
+    #[serde(default)]
+    pub max_active_downloads: Option<usize>,
+    /// which queued downloads are started first as slots free up
+    #[serde(default)]
+    pub queue_order: QueueOrder,
+    /// downloads without progress for this long while actively downloading are removed,
+    /// blocklisted and replaced
+    #[serde(default)]
+    pub stall_minutes: Option<u64>,
+    /// what to do when a relocated file already exists
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+    /// where files replaced during relocation are kept for a while
+    #[serde(default)]
+    pub recycle: RecycleConfig,
+    /// ownership and modes for relocated files
+    #[serde(default)]
+    pub permissions: PermissionsConfig,
+    /// how failed pushes to the sink are retried
+    #[serde(default)]
+    pub retry: RetryConfig,
+    /// how many search decisions to keep per series, see `/api/v1/series/:name/history`
+    #[serde(default = "default_decision_log_size")]
+    pub decision_log_size: usize,
 }
 
 pub fn wipe_nonexistant(db: &Database) -> Result<()> {
     for pull_entry in db.list_pull_entry()? {
-        if !matches!(pull_entry.state, PullState::Finished) {
+        if !pull_entry.state.is_complete() {
             continue;
         }
         let relocate = if let Some(x) = pull_entry.result.relocate_dir() {
@@ -68,24 +151,236 @@ pub fn wipe_nonexistant(db: &Database) -> Result<()> {
     Ok(())
 }
 
+/// Applies `permissions` to every relocate directory known from pull entries
+pub fn fix_permissions(db: &Database, permissions: &PermissionsConfig) -> Result<usize> {
+    if permissions.is_empty() {
+        bail!("no permissions configured");
+    }
+    let roots: HashSet<PathBuf> = db
+        .list_pull_entry()?
+        .into_iter()
+        .filter_map(|x| x.result.relocate.map(PathBuf::from))
+        .collect();
+    let mut count = 0;
+    for root in roots {
+        if root.exists() {
+            info!("fixing permissions in {}", root.display());
+            count += permissions.apply_recursive(&root)?;
+        }
+    }
+    Ok(count)
+}
+
+/// Verifies the files of complete entries, finding moved files by size and checksum.
+/// Unlike `wipe_nonexistant`, nothing is deleted.
+pub fn rescan_library(db: &Database) -> Result<LibraryRescanReport> {
+    let mut report = LibraryRescanReport::default();
+    let mut walked: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    for mut pull_entry in db.list_pull_entry()? {
+        if !pull_entry.state.is_complete() && pull_entry.state != PullState::Missing {
+            continue;
+        }
+        let (Some(relocate), Some(root)) = (
+            pull_entry.result.relocate_dir(),
+            pull_entry.result.relocate.as_ref().map(PathBuf::from),
+        ) else {
+            continue;
+        };
+        let mut moved = vec![];
+        let mut missing = vec![];
+        let mut files = vec![];
+        for file in &pull_entry.files {
+            if relocate.join(file).exists() {
+                files.push(file.clone());
+                continue;
+            }
+            let candidates = match walked.entry(root.clone()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let mut out = vec![];
+                    if root.exists() {
+                        walk_files(&root, &mut out)?;
+                    }
+                    entry.insert(out)
+                }
+            };
+            match find_moved(&pull_entry, file, candidates)? {
+                Some(found) => {
+                    let new_file = relative_to(&found, &relocate).display().to_string();
+                    if let Some(size) = pull_entry.file_sizes.remove(file) {
+                        pull_entry.file_sizes.insert(new_file.clone(), size);
+                    }
+                    moved.push(format!("{file} -> {new_file}"));
+                    files.push(new_file);
+                }
+                None => {
+                    missing.push(file.clone());
+                    files.push(file.clone());
+                }
+            }
+        }
+        pull_entry.files = files;
+
+        let key = pull_entry.key();
+        if !missing.is_empty() {
+            if pull_entry.state != PullState::Missing {
+                info!("files of '{key}' are missing: {}", missing.join(", "));
+                pull_entry.transition(
+                    PullState::Missing,
+                    Some(format!("missing: {}", missing.join(", "))),
+                );
+                report.missing.push(RescannedEntry {
+                    key,
+                    files: missing,
+                });
+            }
+        } else if !moved.is_empty() {
+            info!("files of '{key}' moved: {}", moved.join(", "));
+            pull_entry.transition(
+                PullState::Relocated,
+                Some(format!("moved: {}", moved.join(", "))),
+            );
+            report.relocated.push(RescannedEntry {
+                key,
+                files: pull_entry.files.clone(),
+            });
+        } else if pull_entry.state == PullState::Missing {
+            let state = pull_entry
+                .transitions
+                .iter()
+                .rev()
+                .map(|x| x.state)
+                .find(PullState::is_complete)
+                .unwrap_or(PullState::Finished);
+            pull_entry.transition(state, Some("files found again".to_string()));
+            report.restored.push(RescannedEntry {
+                key,
+                files: pull_entry.files.clone(),
+            });
+        } else {
+            continue;
+        }
+        db.save_pull(&pull_entry)?;
+    }
+    Ok(report)
+}
+
+/// Looks for a moved or renamed file under the series' relocate directory.
+/// A recorded size must match, and for single-file entries so must the release checksum.
+/// Without either, only a file with the same name is accepted.
+fn find_moved(
+    pull_entry: &PullEntry,
+    file: &str,
+    candidates: &[PathBuf],
+) -> Result<Option<PathBuf>> {
+    let size = pull_entry.file_sizes.get(file).copied();
+    let checksum =
+        Some(pull_entry.result.parsed.checksum).filter(|x| *x != 0 && pull_entry.files.len() == 1);
+    if size.is_none() && checksum.is_none() {
+        let name = Path::new(file).file_name();
+        return Ok(candidates.iter().find(|x| x.file_name() == name).cloned());
+    }
+    let sized: Vec<&PathBuf> = candidates
+        .iter()
+        .filter(|x| size.is_none() || std::fs::metadata(x).map(|x| x.len()).ok() == size)
+        .collect();
+    let Some(checksum) = checksum else {
+        // a size alone is only trusted if it is unique
+        return Ok(match &*sized {
+            [found] => Some((*found).clone()),
+            _ => None,
+        });
+    };
+    for candidate in sized {
+        // checksums in release titles are big endian hex
+        if crc32(candidate)?.to_be_bytes() == checksum.to_le_bytes() {
+            return Ok(Some(candidate.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// `path` relative to `base`, both being under the same relocate directory
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    base.components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .chain(path.components().skip(common))
+        .collect()
+}
+
+fn crc32(path: &Path) -> Result<u32> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher.finalize())
+}
+
+const VIDEO_EXTENSIONS: &[&str] = &["mkv", "mp4", "m4v", "avi", "webm", "ts", "wmv", "mov"];
+
+fn walk_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.file_name() == Some(RECYCLE_DIR.as_ref()) {
+            continue;
+        }
+        if path.is_dir() {
+            walk_files(&path, out)?;
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// the first block rule matching a result
+fn blocked(
+    blocks: &[BlockEntry],
+    result: &SearchResult,
+    parsed: Option<&ParsedSearchResult>,
+) -> Option<RejectReason> {
+    let block = blocks.iter().find(|x| x.rule.matches(result, parsed))?;
+    Some(RejectReason::Blocked {
+        rule: block.rule.clone(),
+        reason: block.reason.clone(),
+    })
+}
+
+pub type DynSearcher = Searcher<Arc<dyn Source + Send + Sync>, Box<dyn Sink + Send + Sync>>;
+
 pub struct Searcher<I: Source, O: Sink> {
     source: I,
-    sink: O,
+    sink: Mutex<O>,
     db: Arc<Database>,
     config: SearchConfig,
     search: Arc<Notify>,
     scan: Arc<Notify>,
+    /// sink status of each downloading torrent at the last stall check since startup
+    statuses: Mutex<HashMap<i64, TorrentStatus>>,
 }
 
 impl<I: Source, O: Sink> Searcher<I, O> {
     pub fn new(db: Arc<Database>, source: I, sink: O, config: SearchConfig) -> Result<Self> {
         Ok(Self {
             source,
-            sink,
+            sink: Mutex::new(sink),
             db,
             config,
             search: Arc::new(Notify::new()),
             scan: Arc::new(Notify::new()),
+            statuses: Mutex::new(HashMap::new()),
         })
     }
 
@@ -97,12 +392,18 @@ impl<I: Source, O: Sink> Searcher<I, O> {
         &self.scan
     }
 
-    pub async fn run(mut self) {
+    pub fn source(&self) -> &I {
+        &self.source
+    }
+
+    pub async fn run(self: Arc<Self>) {
         let mut scan_interval = tokio::time::interval(Duration::from_secs(
             self.config.completion_check_minutes * 60,
         ));
         let mut search_interval =
             tokio::time::interval(Duration::from_secs(self.config.search_minutes * 60));
+        let mut retry_interval =
+            tokio::time::interval(Duration::from_secs(self.config.retry.check_seconds));
         loop {
             select! {
                 _ = scan_interval.tick() => {
@@ -125,49 +426,247 @@ impl<I: Source, O: Sink> Searcher<I, O> {
                         error!("failed to run search: {:?}", e);
                     }
                 },
+                _ = retry_interval.tick() => {
+                    if let Err(e) = self.retry_failed().await {
+                        error!("failed to run retries: {:?}", e);
+                    }
+                },
             }
         }
     }
 
-    async fn scan_completed(&mut self) -> Result<()> {
+    async fn scan_completed(&self) -> Result<()> {
         debug!("scan starting");
         self.clean().await?;
 
-        let finished = self.sink.finished().await?;
+        // the sink is locked per call, imports can take a while and progress requests need it
+        let finished = self.sink.lock().await.finished().await?;
         for torrent in finished {
             let Some(mut pull_entry) = self.db.get_pull_entry_from_torrent_id(torrent.id)? else {
                 continue;
             };
             info!("torrent = {:?}, pe = {:?}", torrent, pull_entry);
-            if let Some(relocate) = pull_entry.result.relocate_dir() {
-                let mut download_dir = torrent.download_dir.clone();
-                for (patch, to) in &self.config.path_patch {
-                    if let Some(suffix) = download_dir.strip_prefix(patch) {
-                        download_dir = format!("{to}{suffix}");
-                        break;
-                    }
+            // a retried import only records a transition if it fails differently
+            if pull_entry.state != PullState::Failed {
+                pull_entry.transition(PullState::Importing, None);
+                self.db.save_pull(&pull_entry)?;
+            }
+            // the torrent stays indexed on failure, so the import is retried next scan
+            if let Err(e) = self.import(&mut pull_entry, &torrent).await {
+                error!("failed to import '{}': {:?}", pull_entry.key(), e);
+                let reason = format!("import failed: {e}");
+                if pull_entry.state != PullState::Failed
+                    || pull_entry.reason() != Some(reason.as_str())
+                {
+                    pull_entry.transition(PullState::Failed, Some(reason));
                 }
-                let download_dir = Path::new(&*download_dir);
-                for file in torrent.files {
-                    let new_file = relocate.join(&*file);
-                    let old_file = download_dir.join(&*file);
-                    if old_file.exists() {
-                        tokio::fs::create_dir_all(new_file.parent().unwrap()).await?;
-                        tokio::fs::rename(old_file, new_file).await?;
+                self.db.save_pull(&pull_entry)?;
+                continue;
+            }
+            let state = if pull_entry.result.relocate.is_some() {
+                PullState::Imported
+            } else {
+                PullState::Finished
+            };
+            let conflicts = pull_entry
+                .conflicts
+                .iter()
+                .map(|x| format!("{}: {}", x.file, x.outcome))
+                .collect::<Vec<_>>();
+            pull_entry.transition(state, (!conflicts.is_empty()).then(|| conflicts.join(", ")));
+            self.db.clear_torrent_id(&mut pull_entry)?;
+            self.sink.lock().await.delete(torrent.id).await?;
+        }
+
+        // a recycle directory that can't be cleaned mustn't hold up the stall checks
+        if let Err(e) = self.purge_recycle().await {
+            error!("failed to purge recycle directories: {:?}", e);
+        }
+        self.check_stalled().await?;
+        self.promote_queued().await
+    }
+
+    /// Records download progress, replacing torrents that made none for `stall_minutes`.
+    /// Only time spent actively downloading counts, starting from the first check after
+    /// startup or after the torrent was resumed.
+    async fn check_stalled(&self) -> Result<()> {
+        let now = Utc::now();
+        let mut stalled = false;
+        let mut pulls = self.db.list_pull_entry_downloading()?;
+        pulls.retain(|x| x.state == PullState::Downloading);
+        let stored: Vec<Option<DateTime<Utc>>> = pulls
+            .iter()
+            .map(|x| x.progress.as_ref().map(|x| x.last_progress))
+            .collect();
+        let statuses = self.refresh_progress(&mut pulls).await?;
+        let previous = std::mem::replace(&mut *self.statuses.lock().await, statuses.clone());
+        for (mut pull_entry, stored) in pulls.into_iter().zip(stored) {
+            let Some(torrent_id) = pull_entry.torrent_id else {
+                continue;
+            };
+            let (Some(TorrentStatus::Downloading), Some(progress)) =
+                (statuses.get(&torrent_id), pull_entry.progress.as_mut())
+            else {
+                continue;
+            };
+            if previous.get(&torrent_id) != Some(&TorrentStatus::Downloading) {
+                // the stored time predates a restart or a pause
+                progress.last_progress = now;
+            }
+            let last_progress = progress.last_progress;
+            let stall_minutes = match self.config.stall_minutes {
+                Some(stall_minutes)
+                    if now - last_progress >= chrono::Duration::minutes(stall_minutes as i64) =>
+                {
+                    stall_minutes
+                }
+                _ => {
+                    if stored != Some(last_progress) {
+                        self.db.save_pull(&pull_entry)?;
                     }
-                    pull_entry.files.push(file);
+                    continue;
                 }
-            } else {
-                pull_entry.files.extend(torrent.files);
+            };
+            let reason = format!("stalled: no progress for {stall_minutes} minutes");
+            info!("replacing stalled torrent '{}'", pull_entry.key());
+            if let Err(e) = self.sink.lock().await.delete(torrent_id).await {
+                error!(
+                    "failed to delete stalled torrent '{}': {:?}",
+                    pull_entry.key(),
+                    e
+                );
+                continue;
             }
-            pull_entry.state = PullState::Finished;
+            // the sink's hash, the source may not report one for the blocked release
+            self.db.save_block(&BlockEntry::new(
+                BlockRule::Hash(pull_entry.torrent_hash.clone()),
+                Some(format!("{reason} ({})", pull_entry.result.result.title)),
+            ))?;
+            pull_entry.transition(PullState::Failed, Some(reason));
             self.db.clear_torrent_id(&mut pull_entry)?;
-            self.sink.delete(torrent.id).await?;
+            stalled = true;
+        }
+        if stalled {
+            // look for the next best candidate right away
+            self.search.notify_one();
         }
         Ok(())
     }
 
-    pub async fn clean(&mut self) -> Result<()> {
+    /// Fills in live sink progress for downloading entries, without saving them.
+    /// Returns the sink's status of each torrent found.
+    pub async fn refresh_progress(
+        &self,
+        pulls: &mut [PullEntry],
+    ) -> Result<HashMap<i64, TorrentStatus>> {
+        let mut statuses = HashMap::new();
+        let ids: Vec<i64> = pulls
+            .iter()
+            .filter(|x| x.state == PullState::Downloading)
+            .filter_map(|x| x.torrent_id)
+            .collect();
+        if ids.is_empty() {
+            return Ok(statuses);
+        }
+        let infos = self.sink.lock().await.check_many(&ids).await?;
+        for pull_entry in pulls
+            .iter_mut()
+            .filter(|x| x.state == PullState::Downloading)
+        {
+            let Some(info) = infos.iter().find(|info| {
+                Some(info.id) == pull_entry.torrent_id && info.hash == pull_entry.torrent_hash
+            }) else {
+                continue;
+            };
+            statuses.insert(info.id, info.status);
+            if info.status == TorrentStatus::Finished {
+                continue;
+            }
+            pull_entry.progress = Some(info.progress(pull_entry.progress.as_ref()));
+        }
+        Ok(statuses)
+    }
+
+    /// Moves a finished torrent's files to the pull entry's relocate directory, if any
+    async fn import(&self, pull_entry: &mut PullEntry, torrent: &FinishedTorrent) -> Result<()> {
+        pull_entry.files.clear();
+        pull_entry.file_sizes.clear();
+        pull_entry.conflicts.clear();
+        let Some(relocate) = pull_entry.result.relocate_dir() else {
+            pull_entry.files.extend(torrent.files.iter().cloned());
+            return Ok(());
+        };
+        let mut download_dir = torrent.download_dir.clone();
+        for (patch, to) in &self.config.path_patch {
+            if let Some(suffix) = download_dir.strip_prefix(patch) {
+                download_dir = format!("{to}{suffix}");
+                break;
+            }
+        }
+        let download_dir = Path::new(&*download_dir);
+        let recycle_dir = self.recycle_dir(&pull_entry.result);
+        for file in &torrent.files {
+            let mut new_file = relocate.join(file);
+            let old_file = download_dir.join(file);
+            let mut file = file.clone();
+            if old_file.exists() {
+                let conflict = relocate_file(
+                    &old_file,
+                    &new_file,
+                    &pull_entry.result.result.title,
+                    self.config.conflict_policy,
+                    &recycle_dir,
+                    &self.config.permissions,
+                )
+                .await?;
+                if let Some(outcome) = conflict {
+                    info!("conflict for {}: {}", new_file.display(), outcome);
+                    if let ConflictOutcome::KeptBoth { renamed } = &outcome {
+                        new_file = new_file.with_file_name(renamed);
+                        file = Path::new(&file)
+                            .with_file_name(renamed)
+                            .display()
+                            .to_string();
+                    }
+                    pull_entry.conflicts.push(FileConflict {
+                        file: file.clone(),
+                        outcome,
+                        at: Utc::now(),
+                    });
+                }
+            }
+            if let Ok(metadata) = tokio::fs::metadata(&new_file).await {
+                pull_entry.file_sizes.insert(file.clone(), metadata.len());
+            }
+            pull_entry.files.push(file);
+        }
+        Ok(())
+    }
+
+    fn recycle_dir(&self, result: &ParsedSearchResult) -> PathBuf {
+        match (&self.config.recycle.dir, &result.relocate) {
+            (Some(dir), _) => PathBuf::from(dir),
+            (None, Some(relocate)) => Path::new(relocate).join(RECYCLE_DIR),
+            (None, None) => PathBuf::from(RECYCLE_DIR),
+        }
+    }
+
+    /// Deletes recycled files past `RecycleConfig::retention_days`
+    async fn purge_recycle(&self) -> Result<()> {
+        let mut dirs: HashSet<PathBuf> = HashSet::new();
+        for pull_entry in self.db.list_pull_entry()? {
+            if pull_entry.result.relocate.is_some() {
+                dirs.insert(self.recycle_dir(&pull_entry.result));
+            }
+        }
+        for dir in dirs {
+            purge_recycle(&dir, self.config.recycle.retention_days).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn clean(&self) -> Result<()> {
+        let mut sink = self.sink.lock().await;
         for pull_entry in self.db.list_pull_entry_downloading()? {
             let Some(torrent_id) = pull_entry.torrent_id else {
                 warn!(
@@ -177,102 +676,626 @@ impl<I: Source, O: Sink> Searcher<I, O> {
                 continue;
             };
 
-            match self.sink.check(torrent_id).await? {
-                Some(info) => {
-                    if pull_entry.torrent_hash != info.hash {
-                        info!("removing id-stale torrent: {}", pull_entry.key());
-                        self.db.delete_pull(pull_entry)?;
-                    }
-                    // otherwise inprogress or finished, and not `clean`s concern
+            let reason = match sink.check(torrent_id).await? {
+                Some(info) if pull_entry.torrent_hash != info.hash => {
+                    info!("failing id-stale torrent: {}", pull_entry.key());
+                    "torrent id was reused by another torrent in the sink"
                 }
+                // otherwise inprogress or finished, and not `clean`s concern
+                Some(_) => continue,
                 None => {
-                    info!("removing stale torrent: {}", pull_entry.key());
-                    self.db.delete_pull(pull_entry)?;
+                    info!("failing stale torrent: {}", pull_entry.key());
+                    "torrent was removed from the sink"
                 }
-            }
+            };
+            let mut pull_entry = pull_entry;
+            pull_entry.transition(PullState::Failed, Some(reason.to_string()));
+            self.db.clear_torrent_id(&mut pull_entry)?;
         }
         Ok(())
     }
 
-    async fn run_iter(&mut self) -> Result<()> {
-        info!("round starting");
-        self.clean().await?;
+    /// Order for relocate:
+    /// series > profile > search
+    fn relocate_for(&self, series: &Series, profile: &Profile) -> Option<String> {
+        if series.relocate.is_some() {
+            return series.relocate.clone();
+        }
+        let mut base = if profile.relocate.is_some() {
+            profile.relocate.clone()
+        } else {
+            self.config.relocate.clone()
+        };
+        if let Some(base) = base.as_mut() {
+            if !base.ends_with('/') {
+                base.push('/');
+            }
+            base.push_str(&series.name);
+        }
+        base
+    }
 
-        let mut candidates = vec![];
-        for series in self.db.list_series()? {
-            info!("Searching for {}", series.name);
-            let profile = match self.db.get_profile(&series.profile)? {
-                Some(x) => x,
-                None => {
-                    error!("missing/invalid profile for '{}'", series.name);
+    pub fn get_series_profile(&self, series: &Series) -> Result<Profile> {
+        self.db
+            .get_profile(&series.profile)?
+            .ok_or_else(|| anyhow!("missing/invalid profile for '{}'", series.name))
+    }
+
+    /// Creates `Finished` pull entries for episodes already present in series' relocate directories
+    pub fn import_library(&self, request: &LibraryImportRequest) -> Result<LibraryImportReport> {
+        let mut report = LibraryImportReport::default();
+        let series = match &request.series {
+            Some(name) => vec![self
+                .db
+                .get_series(name)?
+                .ok_or_else(|| anyhow!("series '{name}' not found"))?],
+            None => self.db.list_series()?,
+        };
+        for series in series {
+            let skip = |error: anyhow::Error| LibrarySkippedSeries {
+                series: series.name.clone(),
+                error: error.to_string(),
+            };
+            let profile = match self.get_series_profile(&series) {
+                Ok(x) => x,
+                Err(e) => {
+                    report.skipped.push(skip(e));
                     continue;
                 }
             };
-            let days_old = series
-                .max_days_old
-                .map(|x| self.config.max_days_old.max(x))
-                .unwrap_or(self.config.max_days_old);
+            let Some(root) = self.relocate_for(&series, &profile) else {
+                continue;
+            };
+            let root = PathBuf::from(root);
+            let mut files = vec![];
+            if root.exists() {
+                if let Err(e) = walk_files(&root, &mut files) {
+                    report.skipped.push(skip(e));
+                    continue;
+                }
+            }
+            files.sort();
+            let mut seen = HashSet::new();
+            for path in files {
+                let is_video = path
+                    .extension()
+                    .and_then(|x| x.to_str())
+                    .map(|x| VIDEO_EXTENSIONS.contains(&&*x.to_ascii_lowercase()))
+                    .unwrap_or(false);
+                if !is_video {
+                    continue;
+                }
+                let mut file = LibraryFile {
+                    series: series.name.clone(),
+                    path: path.display().to_string(),
+                    key: None,
+                    error: None,
+                };
+                let pull_entry = match self.library_pull_entry(&series, &profile, &root, &path) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        file.error = Some(e.to_string());
+                        report.unparsed.push(file);
+                        continue;
+                    }
+                };
+                let key = pull_entry.key();
+                file.key = Some(key.clone());
+                if self.db.blocks_new_grab(&key)? || !seen.insert(key) {
+                    report.existing.push(file);
+                    continue;
+                }
+                if !request.dry_run {
+                    // a replaceable entry keeps its history
+                    let pull_entry = match self.db.get_pull_entry(&pull_entry.key())? {
+                        Some(mut existing) => {
+                            existing.result = pull_entry.result;
+                            existing.files = pull_entry.files;
+                            existing.retry = None;
+                            existing.transition(
+                                PullState::Finished,
+                                Some("imported from library".to_string()),
+                            );
+                            existing
+                        }
+                        None => pull_entry,
+                    };
+                    self.db.save_pull(&pull_entry)?;
+                }
+                report.imported.push(file);
+            }
+        }
+        Ok(report)
+    }
+
+    fn library_pull_entry(
+        &self,
+        series: &Series,
+        profile: &Profile,
+        root: &Path,
+        path: &Path,
+    ) -> Result<PullEntry> {
+        let title = path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .ok_or_else(|| anyhow!("file name is not valid UTF-8"))?
+            .to_string();
+        let metadata = std::fs::metadata(path)?;
+        let date: DateTime<Utc> = metadata.modified()?.into();
+        let mut parsed = self.parse_result(
+            series,
+            profile,
+            SearchResult {
+                title,
+                torrent_link: String::new(),
+                view_link: String::new(),
+                date: date.into(),
+                seeders: 0,
+                leechers: 0,
+                downloads: 0,
+                size: metadata.len(),
+                info_hash: None,
+            },
+        )?;
+        parsed.relocate = Some(root.display().to_string());
+        // files outside the season directory are kept relative to the series root
+        let season_dir = parsed.relocate_dir().unwrap_or_else(|| root.to_owned());
+        let file = match path.strip_prefix(&season_dir) {
+            Ok(file) => file,
+            Err(_) => {
+                parsed.relocate_season = false;
+                path.strip_prefix(root)?
+            }
+        };
+        let mut pull_entry = PullEntry::new(
+            parsed,
+            PullState::Finished,
+            Some("imported from library".to_string()),
+        );
+        let file = file.display().to_string();
+        pull_entry.file_sizes.insert(file.clone(), metadata.len());
+        pull_entry.files.push(file);
+        Ok(pull_entry)
+    }
 
+    /// Parses a search result for a series without any filtering, mapping it onto the series' numbering
+    pub fn parse_result(
+        &self,
+        series: &Series,
+        profile: &Profile,
+        result: SearchResult,
+    ) -> Result<ParsedSearchResult> {
+        let parsed = profile.parse_for_series(&result.title, series)?;
+        Ok(ParsedSearchResult {
+            result,
+            parsed,
+            profile: series.profile.to_string(),
+            series: Some(series.name.clone()),
+            relocate: self.relocate_for(series, profile),
+            relocate_season: series.relocate_season,
+        })
+    }
+
+    /// Searches the source for a series, and decides which results would be grabbed.
+    /// Nothing is pushed to the sink.
+    pub async fn evaluate(&self, series: &Series) -> Result<Vec<SearchCandidate>> {
+        let profile = self.get_series_profile(series)?;
+        let days_old = series
+            .max_days_old
+            .map(|x| self.config.max_days_old.max(x))
+            .unwrap_or(self.config.max_days_old);
+
+        // the same release can turn up under several names
+        let mut items = vec![];
+        let mut links = HashSet::new();
+        for name in series.names() {
             let search = match profile.search_prefix.as_ref() {
-                Some(prefix) => format!("{} {}", prefix, series.name),
-                None => series.name.clone(),
+                Some(prefix) => format!("{} {}", prefix, name),
+                None => name.to_string(),
             };
-            match self.source.search(&*search).await {
-                Ok(items) => {
-                    for item in items {
-                        let since = Utc::now().signed_duration_since(item.date);
-                        if since > chrono::Duration::days(days_old as i64)
-                            || item.seeders < self.config.min_seeders
-                        {
-                            continue;
-                        }
-                        let parsed = match profile.parse_name(&*item.title) {
-                            Some(x) => x,
-                            None => {
-                                warn!("failed to parse title: '{}'", item.title);
-                                continue;
-                            }
-                        };
-                        // Order for relocate:
-                        // series > profile > search
-                        let relocate = if series.relocate.is_some() {
-                            series.relocate.clone()
-                        } else {
-                            let mut base = if profile.relocate.is_some() {
-                                profile.relocate.clone()
-                            } else {
-                                self.config.relocate.clone()
-                            };
-                            if let Some(base) = base.as_mut() {
-                                if !base.ends_with('/') {
-                                    base.push('/');
-                                }
-                                base.push_str(&series.name);
-                            }
-                            base
-                        };
-                        candidates.push(ParsedSearchResult {
-                            result: item,
-                            parsed,
-                            profile: series.profile.to_string(),
-                            relocate,
-                            relocate_season: series.relocate_season,
-                        })
-                    }
+            let results = self
+                .source
+                .search(&search)
+                .await
+                .map_err(|e| anyhow!("failure to search '{}': {:?}", search, e))?;
+            items.extend(
+                results
+                    .into_iter()
+                    .filter(|x| links.insert(x.torrent_link.clone())),
+            );
+        }
+
+        let mut items = items
+            .into_iter()
+            .map(|item| {
+                let parsed = self.parse_result(series, &profile, item.clone());
+                (item, parsed)
+            })
+            .collect::<Vec<_>>();
+        // the best ranked release of an episode claims it, the rest become its fallbacks
+        if !profile.rank.is_empty() {
+            items.sort_by_cached_key(|(_, parsed)| {
+                parsed
+                    .as_ref()
+                    .ok()
+                    .map(|x| rank_key(&profile.rank, &x.parsed))
+            });
+        }
+
+        let blocks = self.db.list_block()?;
+        let mut seen = HashSet::new();
+        let mut candidates = vec![];
+        for (item, parsed) in items {
+            let since = Utc::now().signed_duration_since(item.date);
+            let reject = |result: SearchResult, parsed, reason| SearchCandidate {
+                result,
+                parsed,
+                decision: Decision::Reject { reason },
+            };
+            if since > chrono::Duration::days(days_old as i64) {
+                candidates.push(reject(
+                    item,
+                    None,
+                    RejectReason::TooOld {
+                        days_old: since.num_days(),
+                        max_days_old: days_old,
+                    },
+                ));
+                continue;
+            }
+            if item.seeders < self.config.min_seeders {
+                let seeders = item.seeders;
+                candidates.push(reject(
+                    item,
+                    None,
+                    RejectReason::TooFewSeeders {
+                        seeders,
+                        min_seeders: self.config.min_seeders,
+                    },
+                ));
+                continue;
+            }
+            if let Some(reason) = blocked(&blocks, &item, parsed.as_ref().ok()) {
+                candidates.push(reject(item, parsed.ok(), reason));
+                continue;
+            }
+            let parsed = match parsed {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("failed to parse title: '{}'", item.title);
+                    candidates.push(reject(
+                        item,
+                        None,
+                        RejectReason::ParseFailure {
+                            error: e.to_string(),
+                        },
+                    ));
+                    continue;
+                }
+            };
+            if !series.matches_title(&parsed.parsed.title) {
+                let title = parsed.parsed.title.clone();
+                candidates.push(reject(
+                    item,
+                    Some(parsed),
+                    RejectReason::TitleMismatch { title },
+                ));
+                continue;
+            }
+            if let Some(reason) = profile.filter.rejects(&parsed.parsed) {
+                candidates.push(reject(
+                    item,
+                    Some(parsed),
+                    RejectReason::Filtered { reason },
+                ));
+                continue;
+            }
+            if let Some(reason) = self.already_present(series, &parsed)? {
+                candidates.push(reject(item, Some(parsed), reason));
+                continue;
+            }
+            let key = parsed.key();
+            if !seen.insert(key.clone()) {
+                candidates.push(reject(
+                    item,
+                    Some(parsed),
+                    RejectReason::DuplicateCandidate { key },
+                ));
+                continue;
+            }
+            candidates.push(SearchCandidate {
+                result: item,
+                parsed: Some(parsed),
+                decision: Decision::Accept,
+            });
+        }
+        Ok(candidates)
+    }
+
+    /// Rejects an episode that has an entry a new release may not replace. Missing episodes
+    /// are only grabbed again for series with `MissingPolicy::Regrab`.
+    fn already_present(
+        &self,
+        series: &Series,
+        parsed: &ParsedSearchResult,
+    ) -> Result<Option<RejectReason>> {
+        let key = parsed.key();
+        let present = match self.db.get_pull_entry(&key)? {
+            Some(existing) if existing.state == PullState::Missing => {
+                series.missing_policy == MissingPolicy::Keep
+            }
+            Some(existing) => !existing.is_replaceable(),
+            None => false,
+        };
+        Ok(present.then_some(RejectReason::AlreadyPresent { key }))
+    }
+
+    /// Why a result picked by hand may not be grabbed, by the same block and presence rules as
+    /// `evaluate`. Age, seeders and the profile's filter are left to the user's judgement.
+    pub fn check_grab(
+        &self,
+        series: &Series,
+        parsed: &ParsedSearchResult,
+    ) -> Result<Option<RejectReason>> {
+        if let Some(reason) = blocked(&self.db.list_block()?, &parsed.result, Some(parsed)) {
+            return Ok(Some(reason));
+        }
+        self.already_present(series, parsed)
+    }
+
+    /// Pushes a parsed result to the sink and records it as a pull entry.
+    /// If no download slot is free, it is recorded as queued instead.
+    /// Ok(None) -> already present in the sink
+    pub async fn grab(&self, candidate: ParsedSearchResult) -> Result<Option<PullEntry>> {
+        // a replaced entry keeps its history
+        let mut pull_entry = match self.db.get_pull_entry(&candidate.key())? {
+            // pending retries keep counting their attempts
+            Some(mut existing) => {
+                existing.result = candidate;
+                existing.files.clear();
+                existing
+            }
+            None => PullEntry::new(candidate, PullState::Queued, None),
+        };
+        if !self.has_slot(&pull_entry.result)? {
+            info!("queueing download for '{}'", pull_entry.key());
+            if pull_entry.state != PullState::Queued {
+                pull_entry.transition(PullState::Queued, None);
+            }
+            self.db.save_pull(&pull_entry)?;
+            return Ok(Some(pull_entry));
+        }
+        Ok(self.push(&mut pull_entry).await?.then_some(pull_entry))
+    }
+
+    /// Ok(false) -> already present in the sink
+    async fn push(&self, pull_entry: &mut PullEntry) -> Result<bool> {
+        let id = pull_entry.key();
+        info!(
+            "starting download for '{}' from {} ({})",
+            id, pull_entry.result.result.view_link, pull_entry.result.result.date
+        );
+
+        let torrent_info = match self
+            .sink
+            .lock()
+            .await
+            .push(&pull_entry.result.result.torrent_link)
+            .await
+        {
+            Err(e) => {
+                self.record_push_failure(pull_entry, &e)?;
+                return Err(anyhow!("failed to push torrent '{}': {:?}", id, e));
+            }
+            Ok(Some(out)) => out,
+            Ok(None) => {
+                warn!("torrent already present: {}", id);
+                return Ok(false);
+            }
+        };
+        pull_entry.transition(PullState::Downloading, None);
+        pull_entry.retry = None;
+        pull_entry.progress = None;
+        pull_entry.torrent_id = Some(torrent_info.id);
+        pull_entry.torrent_hash = torrent_info.hash;
+        self.db.save_pull(pull_entry)?;
+        self.db.flush().await?;
+        Ok(true)
+    }
+
+    /// Marks a pull entry as failed, scheduling the next retry with exponential backoff.
+    /// Once attempts run out the retry state is cleared, so another release may replace it.
+    fn record_push_failure(&self, pull_entry: &mut PullEntry, error: &anyhow::Error) -> Result<()> {
+        let attempts = pull_entry.retry.as_ref().map(|x| x.attempts).unwrap_or(0) + 1;
+        if attempts >= self.config.retry.max_attempts {
+            warn!(
+                "giving up on '{}' after {} attempts",
+                pull_entry.key(),
+                attempts
+            );
+            pull_entry.retry = None;
+            pull_entry.transition(
+                PullState::Failed,
+                Some(format!(
+                    "push failed (attempt {attempts}, giving up): {error}"
+                )),
+            );
+            return self.db.save_pull(pull_entry);
+        }
+        let backoff = self
+            .config
+            .retry
+            .initial_backoff_seconds
+            .saturating_mul(1 << (attempts - 1).min(32))
+            .min(self.config.retry.max_backoff_seconds);
+        pull_entry.retry = Some(RetryState {
+            attempts,
+            next_attempt: Some(Utc::now() + chrono::Duration::seconds(backoff as i64)),
+        });
+        pull_entry.transition(
+            PullState::Failed,
+            Some(format!("push failed (attempt {attempts}): {error}")),
+        );
+        self.db.save_pull(pull_entry)
+    }
+
+    /// Pushes failed entries whose backoff has elapsed
+    async fn retry_failed(&self) -> Result<()> {
+        let now = Utc::now();
+        for mut pull_entry in self.db.list_pull_entry()? {
+            let due = pull_entry.state == PullState::Failed
+                && pull_entry
+                    .retry
+                    .as_ref()
+                    .and_then(|x| x.next_attempt)
+                    .map(|x| x <= now)
+                    .unwrap_or(false);
+            if !due {
+                continue;
+            }
+            if !self.has_slot(&pull_entry.result)? {
+                info!("queueing retry for '{}'", pull_entry.key());
+                pull_entry.transition(PullState::Queued, None);
+                self.db.save_pull(&pull_entry)?;
+                continue;
+            }
+            info!("retrying '{}'", pull_entry.key());
+            match self.push(&mut pull_entry).await {
+                Ok(true) => (),
+                Ok(false) => {
+                    pull_entry.retry = None;
+                    pull_entry.transition(
+                        PullState::Failed,
+                        Some("torrent already present in sink".to_string()),
+                    );
+                    self.db.save_pull(&pull_entry)?;
+                }
+                Err(e) => error!("{:?}", e),
+            }
+        }
+        Ok(())
+    }
+
+    /// true if neither the global nor the series' download limit is reached
+    fn has_slot(&self, result: &ParsedSearchResult) -> Result<bool> {
+        let series_limit = match &result.series {
+            Some(name) => self
+                .db
+                .get_series(name)?
+                .and_then(|series| series.max_active_downloads),
+            None => None,
+        };
+        if self.config.max_active_downloads.is_none() && series_limit.is_none() {
+            return Ok(true);
+        }
+        let active = self.db.list_pull_entry_downloading()?;
+        if let Some(limit) = self.config.max_active_downloads {
+            if active.len() >= limit {
+                return Ok(false);
+            }
+        }
+        if let Some(limit) = series_limit {
+            let series_active = active
+                .iter()
+                .filter(|x| x.result.series == result.series)
+                .count();
+            if series_active >= limit {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Pushes queued entries, in priority order, while download slots are free
+    async fn promote_queued(&self) -> Result<()> {
+        let mut queued = self
+            .db
+            .list_pull_entry()?
+            .into_iter()
+            .filter(|x| x.state == PullState::Queued)
+            .collect::<Vec<_>>();
+        queued.sort_by(|a, b| {
+            let order = (a.result.parsed.season, &a.result.parsed.episode)
+                .cmp(&(b.result.parsed.season, &b.result.parsed.episode));
+            match self.config.queue_order {
+                QueueOrder::NewestFirst => order.reverse(),
+                QueueOrder::OldestFirst => order,
+            }
+        });
+        for mut pull_entry in queued {
+            if !self.has_slot(&pull_entry.result)? {
+                continue;
+            }
+            match self.push(&mut pull_entry).await {
+                Ok(true) => (),
+                Ok(false) => {
+                    pull_entry.retry = None;
+                    pull_entry.transition(
+                        PullState::Failed,
+                        Some("torrent already present in sink".to_string()),
+                    );
+                    self.db.save_pull(&pull_entry)?;
                 }
+                Err(e) => error!("failed to promote '{}': {:?}", pull_entry.key(), e),
+            }
+        }
+        Ok(())
+    }
+
+    async fn run_iter(&self) -> Result<()> {
+        info!("round starting");
+        self.clean().await?;
+
+        for series in self.db.list_series()? {
+            info!("Searching for {}", series.name);
+            let candidates = match self.evaluate(&series).await {
+                Ok(x) => x,
                 Err(e) => {
-                    error!("failure to search '{}': {:?}", search, e);
+                    error!("failed to search for '{}': {:?}", series.name, e);
+                    continue;
                 }
+            };
+            let candidates = self.grab_candidates(candidates).await;
+            let at = Utc::now();
+            let records = candidates
+                .into_iter()
+                .map(|candidate| DecisionRecord {
+                    series: series.name.clone(),
+                    at,
+                    key: candidate.parsed.as_ref().map(ParsedSearchResult::key),
+                    title: candidate.result.title,
+                    view_link: candidate.result.view_link,
+                    decision: candidate.decision,
+                })
+                .collect::<Vec<_>>();
+            if let Err(e) =
+                self.db
+                    .save_decisions(&series.name, &records, self.config.decision_log_size)
+            {
+                error!("failed to save decisions for '{}': {:?}", series.name, e);
             }
         }
+        Ok(())
+    }
+
+    /// Records an ignored episode, so it shows in the series status
+    fn ignore(&self, parsed: &ParsedSearchResult, reason: Option<String>) -> Result<()> {
+        if self.db.exists_pull_entry(&parsed.key())? {
+            return Ok(());
+        }
+        self.db
+            .save_pull(&PullEntry::new(parsed.clone(), PullState::Ignored, reason))
+    }
 
+    /// Grabs accepted candidates, updating their decisions with the outcome of the push
+    async fn grab_candidates(&self, mut candidates: Vec<SearchCandidate>) -> Vec<SearchCandidate> {
         info!("found {} candidates", candidates.len());
         debug!(
             "{:<115} {:<5} {:<7} {:<7} {:<9} {:<9} {:<15}",
             "TITLE", "DATE", "SEEDERS", "LEECHERS", "DOWNLOADS", "SIZE (MB)", "VIEW"
         );
-        for candidate in candidates {
-            let id = candidate.key();
+        let mut grabbed = HashSet::new();
+        for candidate in &mut candidates {
             debug!(
                 "{:<115} {:<5} {:>7} {:>7} {:>9} {: >9.02} {:<15}",
                 candidate.result.title,
@@ -283,35 +1306,654 @@ impl<I: Source, O: Sink> Searcher<I, O> {
                 (candidate.result.size as f64) / 1024.0 / 1024.0,
                 candidate.result.view_link
             );
-            if self.db.exists_pull_entry(&id)? {
+            let Some(parsed) = &candidate.parsed else {
                 continue;
-            }
-            info!(
-                "starting download for '{}' from {} ({})",
-                id, candidate.result.view_link, candidate.result.date
-            );
-
-            let torrent_info = match self.sink.push(&*candidate.result.torrent_link).await {
-                Err(e) => {
-                    error!("failed to push torrent '{}': {:?}", id, e);
+            };
+            // duplicates are fallbacks for when an earlier candidate for the episode fails to push
+            match &candidate.decision {
+                Decision::Accept
+                | Decision::Reject {
+                    reason: RejectReason::DuplicateCandidate { .. },
+                } => (),
+                Decision::Reject {
+                    reason:
+                        RejectReason::Blocked {
+                            rule: BlockRule::Episode(_),
+                            reason,
+                        },
+                } => {
+                    if let Err(e) = self.ignore(parsed, reason.clone()) {
+                        error!("failed to ignore '{}': {:?}", parsed.key(), e);
+                    }
                     continue;
                 }
-                Ok(Some(out)) => out,
-                Ok(None) => {
-                    warn!("torrent already present: {}", id);
-                    continue;
+                Decision::Reject { .. } => continue,
+            }
+            let key = parsed.key();
+            if grabbed.contains(&key) {
+                continue;
+            }
+            candidate.decision = match self.grab(parsed.clone()).await {
+                Ok(Some(_)) => {
+                    grabbed.insert(key);
+                    Decision::Accept
+                }
+                Ok(None) => Decision::Reject {
+                    reason: RejectReason::AlreadyInSink,
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    Decision::Reject {
+                        reason: RejectReason::PushFailed {
+                            error: e.to_string(),
+                        },
+                    }
                 }
             };
-            let pull_entry = PullEntry {
-                result: candidate,
-                torrent_id: Some(torrent_info.id),
-                torrent_hash: torrent_info.hash,
-                state: PullState::Downloading,
-                files: vec![],
-            };
-            self.db.save_pull(&pull_entry)?;
-            self.db.flush().await?;
         }
-        Ok(())
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        testing::{pull, series, temporary},
+        DbBackend,
+    };
+    use crate::sink::TorrentInfo;
+    use serde_json::json;
+
+    /// Finds the same results for every query
+    struct FakeSource(Vec<SearchResult>);
+
+    #[async_trait::async_trait]
+    impl Source for FakeSource {
+        async fn search(&self, _query: &str) -> Result<Vec<SearchResult>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    struct FakeTorrent {
+        id: i64,
+        link: String,
+        status: TorrentStatus,
+    }
+
+    /// Keeps torrents in memory, each pushed link becomes a torrent with one file named after it
+    #[derive(Default)]
+    struct FakeSink {
+        torrents: Vec<FakeTorrent>,
+        next_id: i64,
+        /// fail every push, as if the sink was unreachable
+        push_error: bool,
+        /// fail every delete
+        delete_error: bool,
+    }
+
+    impl FakeSink {
+        fn info(torrent: &FakeTorrent) -> TorrentInfo {
+            TorrentInfo {
+                id: torrent.id,
+                hash: format!("hash-{}", torrent.id),
+                status: torrent.status,
+                percent_done: 0.0,
+                peers: 0,
+                rate_download: 0,
+                eta_seconds: None,
+                size: 0,
+            }
+        }
+
+        fn links(&self) -> Vec<&str> {
+            self.torrents.iter().map(|x| &*x.link).collect()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for FakeSink {
+        async fn push(&mut self, torrent_url: &str) -> Result<Option<TorrentInfo>> {
+            if self.push_error {
+                return Err(anyhow!("connection refused"));
+            }
+            if self.torrents.iter().any(|x| x.link == torrent_url) {
+                return Ok(None);
+            }
+            self.next_id += 1;
+            self.torrents.push(FakeTorrent {
+                id: self.next_id,
+                link: torrent_url.to_string(),
+                status: TorrentStatus::Downloading,
+            });
+            Ok(self.torrents.last().map(Self::info))
+        }
+
+        async fn check(&mut self, id: i64) -> Result<Option<TorrentInfo>> {
+            Ok(self.torrents.iter().find(|x| x.id == id).map(Self::info))
+        }
+
+        async fn check_many(&mut self, ids: &[i64]) -> Result<Vec<TorrentInfo>> {
+            Ok(self
+                .torrents
+                .iter()
+                .filter(|x| ids.contains(&x.id))
+                .map(Self::info)
+                .collect())
+        }
+
+        async fn finished(&mut self) -> Result<Vec<FinishedTorrent>> {
+            Ok(self
+                .torrents
+                .iter()
+                .filter(|x| x.status == TorrentStatus::Finished)
+                .map(|x| FinishedTorrent {
+                    id: x.id,
+                    download_dir: "/downloads".to_string(),
+                    files: vec![format!("{}.mkv", x.link)],
+                })
+                .collect())
+        }
+
+        async fn delete(&mut self, id: i64) -> Result<()> {
+            if self.delete_error {
+                return Err(anyhow!("connection refused"));
+            }
+            self.torrents.retain(|x| x.id != id);
+            Ok(())
+        }
+    }
+
+    fn config() -> SearchConfig {
+        SearchConfig {
+            max_days_old: 30,
+            min_seeders: 1,
+            search_minutes: 60,
+            completion_check_minutes: 5,
+            source: default_source_sink(),
+            sink: default_source_sink(),
+            path_patch: IndexMap::new(),
+            relocate: None,
+            max_active_downloads: None,
+            queue_order: QueueOrder::default(),
+            stall_minutes: None,
+            conflict_policy: ConflictPolicy::default(),
+            recycle: RecycleConfig::default(),
+            permissions: PermissionsConfig::default(),
+            retry: RetryConfig::default(),
+            decision_log_size: default_decision_log_size(),
+        }
+    }
+
+    /// the `group` profile with the built-in parser, relocating to `relocate`/<series-name>
+    fn profile(relocate: &Path) -> Profile {
+        serde_json::from_value(json!({
+            "name": "group",
+            "search_prefix": null,
+            "parser": "auto",
+            "relocate": relocate,
+        }))
+        .unwrap()
+    }
+
+    fn searcher(config: SearchConfig) -> Searcher<FakeSource, FakeSink> {
+        let db = Arc::new(temporary(DbBackend::Sled));
+        Searcher::new(db, FakeSource(vec![]), FakeSink::default(), config).unwrap()
+    }
+
+    /// episode `episode` of series `title`, linking to `{title}-{episode}`
+    fn candidate(title: &str, episode: u32) -> ParsedSearchResult {
+        let mut result = pull(title, Some(title), episode, PullState::Queued, None).result;
+        result.result.torrent_link = format!("{title}-{episode}");
+        result
+    }
+
+    fn state<I: Source, O: Sink>(searcher: &Searcher<I, O>, key: &str) -> PullState {
+        searcher.db.get_pull_entry(key).unwrap().unwrap().state
+    }
+
+    #[tokio::test]
+    async fn test_global_download_limit() {
+        let searcher = searcher(SearchConfig {
+            max_active_downloads: Some(2),
+            ..config()
+        });
+        for episode in 1..=3 {
+            searcher.grab(candidate("Foo", episode)).await.unwrap();
+        }
+        assert_eq!(state(&searcher, "Foo_S01E01"), PullState::Downloading);
+        assert_eq!(state(&searcher, "Foo_S01E02"), PullState::Downloading);
+        assert_eq!(state(&searcher, "Foo_S01E03"), PullState::Queued);
+        assert_eq!(searcher.sink.lock().await.links(), vec!["Foo-1", "Foo-2"]);
+    }
+
+    #[tokio::test]
+    async fn test_series_download_limit() {
+        let searcher = searcher(config());
+        let mut foo = series("Foo");
+        foo.max_active_downloads = Some(1);
+        searcher.db.save_series(&foo).unwrap();
+        searcher.db.save_series(&series("Bar")).unwrap();
+        for candidate in [
+            candidate("Foo", 1),
+            candidate("Foo", 2),
+            candidate("Bar", 1),
+        ] {
+            searcher.grab(candidate).await.unwrap();
+        }
+        assert_eq!(state(&searcher, "Foo_S01E01"), PullState::Downloading);
+        assert_eq!(state(&searcher, "Foo_S01E02"), PullState::Queued);
+        // other series aren't held back by Foo's limit
+        assert_eq!(state(&searcher, "Bar_S01E01"), PullState::Downloading);
+    }
+
+    /// A finished download frees one slot, taken by the first queued entry in `queue_order`
+    #[tokio::test]
+    async fn test_promote_queued() {
+        for (queue_order, promoted, waiting) in [
+            (QueueOrder::NewestFirst, "Foo_S01E03", "Foo_S01E02"),
+            (QueueOrder::OldestFirst, "Foo_S01E02", "Foo_S01E03"),
+        ] {
+            let searcher = searcher(SearchConfig {
+                max_active_downloads: Some(1),
+                queue_order,
+                ..config()
+            });
+            for episode in 1..=3 {
+                searcher.grab(candidate("Foo", episode)).await.unwrap();
+            }
+            assert_eq!(state(&searcher, "Foo_S01E03"), PullState::Queued);
+            searcher.sink.lock().await.torrents[0].status = TorrentStatus::Finished;
+            searcher.scan_completed().await.unwrap();
+
+            let finished = searcher.db.get_pull_entry("Foo_S01E01").unwrap().unwrap();
+            assert_eq!(finished.state, PullState::Finished);
+            assert_eq!(finished.files, vec!["Foo-1.mkv"]);
+            assert_eq!(state(&searcher, promoted), PullState::Downloading);
+            assert_eq!(state(&searcher, waiting), PullState::Queued);
+            assert_eq!(searcher.sink.lock().await.torrents.len(), 1);
+        }
+    }
+
+    /// Fails one push of `candidate`, returning the seconds until its next attempt
+    async fn fail_push<I: Source>(
+        searcher: &Searcher<I, FakeSink>,
+        candidate: ParsedSearchResult,
+    ) -> Option<i64> {
+        let key = candidate.key();
+        let before = Utc::now();
+        assert!(searcher.grab(candidate).await.is_err());
+        let pull_entry = searcher.db.get_pull_entry(&key).unwrap().unwrap();
+        assert_eq!(pull_entry.state, PullState::Failed);
+        let next_attempt = pull_entry.retry?.next_attempt.unwrap();
+        Some((next_attempt - before).num_seconds())
+    }
+
+    #[tokio::test]
+    async fn test_push_backoff() {
+        let searcher = searcher(SearchConfig {
+            retry: RetryConfig {
+                max_attempts: 5,
+                initial_backoff_seconds: 60,
+                max_backoff_seconds: 200,
+                check_seconds: 60,
+            },
+            ..config()
+        });
+        searcher.sink.lock().await.push_error = true;
+        // doubles from the initial backoff, capped at the maximum
+        for (attempts, backoff) in [(1, 60), (2, 120), (3, 200), (4, 200)] {
+            let seconds = fail_push(&searcher, candidate("Foo", 1)).await.unwrap();
+            assert!((backoff..=backoff + 1).contains(&seconds), "{seconds}");
+            let pull_entry = searcher.db.get_pull_entry("Foo_S01E01").unwrap().unwrap();
+            assert_eq!(pull_entry.retry.as_ref().unwrap().attempts, attempts);
+            assert!(!pull_entry.is_replaceable());
+        }
+
+        // the last attempt gives up, so another release may take the episode's place
+        assert_eq!(fail_push(&searcher, candidate("Foo", 1)).await, None);
+        let pull_entry = searcher.db.get_pull_entry("Foo_S01E01").unwrap().unwrap();
+        assert!(pull_entry
+            .reason()
+            .unwrap()
+            .contains("attempt 5, giving up"));
+        assert!(pull_entry.is_replaceable());
+        assert_eq!(
+            searcher
+                .check_grab(&series("Foo"), &candidate("Foo", 1))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grab_over_failed() {
+        let searcher = searcher(config());
+        searcher.sink.lock().await.push_error = true;
+        fail_push(&searcher, candidate("Foo", 1)).await.unwrap();
+        fail_push(&searcher, candidate("Foo", 1)).await.unwrap();
+
+        searcher.sink.lock().await.push_error = false;
+        let pull_entry = searcher.grab(candidate("Foo", 1)).await.unwrap().unwrap();
+        assert_eq!(pull_entry.state, PullState::Downloading);
+        assert!(pull_entry.retry.is_none());
+        assert_eq!(pull_entry.torrent_id, Some(1));
+        // the failed attempts stay in the entry's history
+        let states = pull_entry
+            .transitions
+            .iter()
+            .map(|x| x.state)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            vec![
+                PullState::Queued,
+                PullState::Failed,
+                PullState::Failed,
+                PullState::Downloading
+            ]
+        );
+    }
+
+    /// Moves the stored last progress of `key` an hour back
+    fn age_progress<I: Source, O: Sink>(searcher: &Searcher<I, O>, key: &str) {
+        let mut pull_entry = searcher.db.get_pull_entry(key).unwrap().unwrap();
+        pull_entry.progress.as_mut().unwrap().last_progress -= chrono::Duration::hours(1);
+        searcher.db.save_pull(&pull_entry).unwrap();
+    }
+
+    fn stalling() -> SearchConfig {
+        SearchConfig {
+            stall_minutes: Some(10),
+            ..config()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stalled_after_restart() {
+        let searcher = searcher(stalling());
+        searcher.grab(candidate("Foo", 1)).await.unwrap();
+        searcher.check_stalled().await.unwrap();
+
+        // progress stored before a restart doesn't count
+        age_progress(&searcher, "Foo_S01E01");
+        searcher.statuses.lock().await.clear();
+        searcher.check_stalled().await.unwrap();
+        let pull_entry = searcher.db.get_pull_entry("Foo_S01E01").unwrap().unwrap();
+        assert_eq!(pull_entry.state, PullState::Downloading);
+        assert!(
+            Utc::now() - pull_entry.progress.unwrap().last_progress < chrono::Duration::minutes(1)
+        );
+
+        age_progress(&searcher, "Foo_S01E01");
+        searcher.check_stalled().await.unwrap();
+        let pull_entry = searcher.db.get_pull_entry("Foo_S01E01").unwrap().unwrap();
+        assert_eq!(pull_entry.state, PullState::Failed);
+        assert!(pull_entry.reason().unwrap().starts_with("stalled"));
+        assert!(pull_entry.is_replaceable());
+        assert!(searcher.sink.lock().await.torrents.is_empty());
+        let blocks = searcher.db.list_block().unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert!(matches!(&blocks[0].rule, BlockRule::Hash(hash) if hash == "hash-1"));
+    }
+
+    #[tokio::test]
+    async fn test_idle_not_stalled() {
+        let searcher = searcher(stalling());
+        searcher.grab(candidate("Foo", 1)).await.unwrap();
+        searcher.check_stalled().await.unwrap();
+
+        // paused, queued or verifying
+        searcher.sink.lock().await.torrents[0].status = TorrentStatus::Idle;
+        for _ in 0..2 {
+            age_progress(&searcher, "Foo_S01E01");
+            searcher.check_stalled().await.unwrap();
+            assert_eq!(state(&searcher, "Foo_S01E01"), PullState::Downloading);
+        }
+
+        // resumed, counting from now
+        searcher.sink.lock().await.torrents[0].status = TorrentStatus::Downloading;
+        searcher.check_stalled().await.unwrap();
+        assert_eq!(state(&searcher, "Foo_S01E01"), PullState::Downloading);
+        age_progress(&searcher, "Foo_S01E01");
+        searcher.check_stalled().await.unwrap();
+        assert_eq!(state(&searcher, "Foo_S01E01"), PullState::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_delete_error() {
+        let searcher = searcher(stalling());
+        searcher.grab(candidate("Foo", 1)).await.unwrap();
+        searcher.grab(candidate("Foo", 2)).await.unwrap();
+        searcher.check_stalled().await.unwrap();
+        age_progress(&searcher, "Foo_S01E01");
+        age_progress(&searcher, "Foo_S01E02");
+
+        // both are tried, and kept until the sink lets go of them
+        searcher.sink.lock().await.delete_error = true;
+        searcher.check_stalled().await.unwrap();
+        assert_eq!(state(&searcher, "Foo_S01E01"), PullState::Downloading);
+        assert_eq!(state(&searcher, "Foo_S01E02"), PullState::Downloading);
+        assert!(searcher.db.list_block().unwrap().is_empty());
+
+        searcher.sink.lock().await.delete_error = false;
+        searcher.check_stalled().await.unwrap();
+        assert_eq!(state(&searcher, "Foo_S01E01"), PullState::Failed);
+        assert_eq!(state(&searcher, "Foo_S01E02"), PullState::Failed);
+    }
+
+    #[test]
+    fn test_import_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let searcher = searcher(config());
+        searcher.db.save_profile(&profile(dir.path())).unwrap();
+        searcher.db.save_series(&series("Foo")).unwrap();
+        let season = dir.path().join("Foo/Season 1");
+        std::fs::create_dir_all(&season).unwrap();
+        for name in [
+            "[Group] Foo - 01 (1080p).mkv",
+            "[Group] Foo - 02 (1080p).mkv",
+            "[Group] Foo - 01 (1080p).ass",
+            "Foo.mkv",
+        ] {
+            std::fs::write(season.join(name), name).unwrap();
+        }
+        let downloading = pull("Foo", Some("Foo"), 2, PullState::Downloading, Some(1));
+        searcher.db.save_pull(&downloading).unwrap();
+
+        let mut request = LibraryImportRequest {
+            dry_run: true,
+            series: None,
+        };
+        let report = searcher.import_library(&request).unwrap();
+        let keys = |files: &[LibraryFile]| {
+            files
+                .iter()
+                .map(|x| x.key.clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(&report.imported), vec!["Foo_S01E01"]);
+        assert_eq!(keys(&report.existing), vec!["Foo_S01E02"]);
+        assert_eq!(report.unparsed.len(), 1);
+        assert!(report.unparsed[0].path.ends_with("Foo.mkv"));
+        assert!(searcher.db.get_pull_entry("Foo_S01E01").unwrap().is_none());
+
+        request.dry_run = false;
+        let report = searcher.import_library(&request).unwrap();
+        assert_eq!(keys(&report.imported), vec!["Foo_S01E01"]);
+        let imported = searcher.db.get_pull_entry("Foo_S01E01").unwrap().unwrap();
+        assert_eq!(imported.state, PullState::Finished);
+        assert_eq!(imported.files, vec!["[Group] Foo - 01 (1080p).mkv"]);
+        assert_eq!(imported.file_sizes["[Group] Foo - 01 (1080p).mkv"], 28);
+        assert_eq!(
+            imported.result.relocate_dir(),
+            Some(dir.path().join("Foo/Season 1"))
+        );
+        assert_eq!(state(&searcher, "Foo_S01E02"), PullState::Downloading);
+
+        // imported entries are existing ones on the next import
+        let report = searcher.import_library(&request).unwrap();
+        assert!(report.imported.is_empty());
+        assert_eq!(keys(&report.existing), vec!["Foo_S01E01", "Foo_S01E02"]);
+    }
+
+    /// a finished entry for episode `episode` of Foo, with `files` relocated under `root`
+    fn relocated(root: &Path, episode: u32, files: &[&str]) -> PullEntry {
+        let mut pull_entry = pull("Foo", Some("Foo"), episode, PullState::Finished, None);
+        pull_entry.result.relocate = Some(root.display().to_string());
+        pull_entry.files = files.iter().map(|x| x.to_string()).collect();
+        pull_entry
+    }
+
+    fn write(path: &Path, contents: &str) -> PathBuf {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+        path.to_owned()
+    }
+
+    #[test]
+    fn test_rescan_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = temporary(DbBackend::Sled);
+        write(&dir.path().join("Season 1/a.mkv"), "a");
+        write(&dir.path().join("Extras/b.mkv"), "b");
+        db.save_pull(&relocated(dir.path(), 1, &["a.mkv"])).unwrap();
+        db.save_pull(&relocated(dir.path(), 2, &["b.mkv"])).unwrap();
+        db.save_pull(&relocated(dir.path(), 3, &["c.mkv"])).unwrap();
+
+        let report = rescan_library(&db).unwrap();
+        let keys = |entries: &[RescannedEntry]| {
+            entries
+                .iter()
+                .map(|x| (x.key.clone(), x.files.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys(&report.relocated),
+            vec![(
+                "Foo_S01E02".to_string(),
+                vec!["../Extras/b.mkv".to_string()]
+            )]
+        );
+        assert_eq!(
+            keys(&report.missing),
+            vec![("Foo_S01E03".to_string(), vec!["c.mkv".to_string()])]
+        );
+        assert!(report.restored.is_empty());
+        let get = |key| db.get_pull_entry(key).unwrap().unwrap();
+        assert_eq!(get("Foo_S01E01").state, PullState::Finished);
+        assert_eq!(get("Foo_S01E02").state, PullState::Relocated);
+        assert_eq!(get("Foo_S01E02").files, vec!["../Extras/b.mkv"]);
+        assert_eq!(get("Foo_S01E03").state, PullState::Missing);
+
+        // still missing entries aren't reported again, found ones are restored
+        assert!(rescan_library(&db).unwrap().missing.is_empty());
+        write(&dir.path().join("Season 1/c.mkv"), "c");
+        let report = rescan_library(&db).unwrap();
+        assert_eq!(
+            keys(&report.restored),
+            vec![("Foo_S01E03".to_string(), vec!["c.mkv".to_string()])]
+        );
+        assert_eq!(get("Foo_S01E03").state, PullState::Finished);
+    }
+
+    #[test]
+    fn test_find_moved_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let candidates = vec![
+            write(&dir.path().join("one.mkv"), "12345"),
+            write(&dir.path().join("two.mkv"), "1234567"),
+            write(&dir.path().join("three.mkv"), "1234567"),
+        ];
+        let mut pull_entry = relocated(dir.path(), 1, &["old.mkv"]);
+        pull_entry.file_sizes.insert("old.mkv".to_string(), 5);
+        assert_eq!(
+            find_moved(&pull_entry, "old.mkv", &candidates).unwrap(),
+            Some(candidates[0].clone())
+        );
+        // ambiguous sizes are left alone
+        pull_entry.file_sizes.insert("old.mkv".to_string(), 7);
+        assert_eq!(
+            find_moved(&pull_entry, "old.mkv", &candidates).unwrap(),
+            None
+        );
+
+        // without a size only the same name matches
+        pull_entry.file_sizes.clear();
+        assert_eq!(
+            find_moved(&pull_entry, "old.mkv", &candidates).unwrap(),
+            None
+        );
+        assert_eq!(
+            find_moved(&pull_entry, "Season 1/two.mkv", &candidates).unwrap(),
+            Some(candidates[1].clone())
+        );
+    }
+
+    #[test]
+    fn test_find_moved_by_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let candidates = vec![
+            write(&dir.path().join("one.mkv"), "1234567"),
+            write(&dir.path().join("two.mkv"), "7654321"),
+        ];
+        // as written in the release title
+        let checksum = format!("{:08X}", crc32fast::hash(b"7654321"));
+        let title = format!("[Group] Foo - 01 [{checksum}].mkv");
+        let parsed = profile(dir.path()).try_parse_name(&title).unwrap();
+        assert_ne!(parsed.checksum, 0);
+
+        let mut pull_entry = relocated(dir.path(), 1, &["old.mkv"]);
+        pull_entry.file_sizes.insert("old.mkv".to_string(), 7);
+        pull_entry.result.parsed.checksum = parsed.checksum;
+        assert_eq!(
+            find_moved(&pull_entry, "old.mkv", &candidates).unwrap(),
+            Some(candidates[1].clone())
+        );
+        // the checksum has to match, not just the size
+        pull_entry.result.parsed.checksum = parsed.checksum.swap_bytes();
+        assert_eq!(
+            find_moved(&pull_entry, "old.mkv", &candidates).unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_missing_policy() {
+        let mut result = candidate("Foo", 1).result;
+        result.date = Utc::now().into();
+        for (missing_policy, accepted) in
+            [(MissingPolicy::Keep, false), (MissingPolicy::Regrab, true)]
+        {
+            let db = Arc::new(temporary(DbBackend::Sled));
+            let searcher = Searcher::new(
+                db,
+                FakeSource(vec![result.clone()]),
+                FakeSink::default(),
+                config(),
+            )
+            .unwrap();
+            searcher
+                .db
+                .save_profile(&profile(Path::new("/library")))
+                .unwrap();
+            let mut foo = series("Foo");
+            foo.missing_policy = missing_policy;
+            searcher.db.save_series(&foo).unwrap();
+            let missing = pull("Foo", Some("Foo"), 1, PullState::Missing, None);
+            searcher.db.save_pull(&missing).unwrap();
+
+            let candidates = searcher.evaluate(&foo).await.unwrap();
+            assert_eq!(candidates.len(), 1);
+            match &candidates[0].decision {
+                Decision::Accept => assert!(accepted),
+                Decision::Reject { reason } => {
+                    assert!(!accepted);
+                    assert!(
+                        matches!(reason, RejectReason::AlreadyPresent { key } if key == "Foo_S01E01")
+                    );
+                }
+            }
+        }
     }
 }
@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 pub use transmission::*;
 
 use anyhow::Result;
+use chrono::Utc;
+use nyauser_types::DownloadProgress;
 
 #[derive(Debug)]
 pub struct FinishedTorrent {
@@ -11,15 +13,46 @@ pub struct FinishedTorrent {
     pub files: Vec<String>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TorrentStatus {
     Finished,
-    InProgress,
+    /// actively downloading
+    Downloading,
+    /// paused, queued or verifying
+    Idle,
 }
 
 pub struct TorrentInfo {
     pub id: i64,
     pub hash: String,
     pub status: TorrentStatus,
+    /// 0.0 to 1.0
+    pub percent_done: f32,
+    pub peers: u64,
+    /// bytes per second
+    pub rate_download: u64,
+    /// seconds until done, if the sink can estimate it
+    pub eta_seconds: Option<u64>,
+    /// bytes to download
+    pub size: u64,
+}
+
+impl TorrentInfo {
+    /// `previous` carries over the last time any progress was made
+    pub fn progress(&self, previous: Option<&DownloadProgress>) -> DownloadProgress {
+        let last_progress = match previous {
+            Some(previous) if self.percent_done <= previous.percent_done => previous.last_progress,
+            _ => Utc::now(),
+        };
+        DownloadProgress {
+            percent_done: self.percent_done,
+            peers: self.peers,
+            rate_download: self.rate_download,
+            eta_seconds: self.eta_seconds,
+            size: self.size,
+            last_progress,
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -29,6 +62,9 @@ pub trait Sink: Send + Sync {
 
     async fn check(&mut self, id: i64) -> Result<Option<TorrentInfo>>;
 
+    /// `check` for many torrents at once, missing torrents are omitted
+    async fn check_many(&mut self, ids: &[i64]) -> Result<Vec<TorrentInfo>>;
+
     async fn finished(&mut self) -> Result<Vec<FinishedTorrent>>;
 
     async fn delete(&mut self, id: i64) -> Result<()>;
@@ -44,6 +80,10 @@ impl Sink for Box<dyn Sink + Send + Sync> {
         Sink::check(&mut **self, id).await
     }
 
+    async fn check_many(&mut self, ids: &[i64]) -> Result<Vec<TorrentInfo>> {
+        Sink::check_many(&mut **self, ids).await
+    }
+
     async fn finished(&mut self) -> Result<Vec<FinishedTorrent>> {
         Sink::finished(&mut **self).await
     }
@@ -9,6 +9,9 @@ use anyhow::Result;
 
 use super::{FinishedTorrent, TorrentInfo, TorrentStatus};
 
+/// transmission's `status` of an actively downloading torrent
+const STATUS_DOWNLOAD: i64 = 4;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TransmissionConfig {
     pub url: String,
@@ -40,7 +43,12 @@ impl TorrentInfo {
             TorrentAddedOrDuplicate::TorrentAdded(torrent) => Some(Self {
                 id: torrent.id?,
                 hash: torrent.hash_string.clone()?,
-                status: TorrentStatus::InProgress,
+                status: TorrentStatus::Downloading,
+                percent_done: 0.0,
+                peers: 0,
+                rate_download: 0,
+                eta_seconds: None,
+                size: 0,
             }),
             TorrentAddedOrDuplicate::TorrentDuplicate(_) => None,
         }
@@ -68,33 +76,57 @@ impl Sink for TransmissionClient {
     }
 
     async fn check(&mut self, id: i64) -> Result<Option<TorrentInfo>> {
-        let torrent = self
+        Ok(self.check_many(&[id]).await?.into_iter().next())
+    }
+
+    async fn check_many(&mut self, ids: &[i64]) -> Result<Vec<TorrentInfo>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let torrents = self
             .client
             .torrent_get(
                 Some(vec![
                     TorrentGetField::Id,
                     TorrentGetField::IsFinished,
+                    TorrentGetField::Status,
                     TorrentGetField::PercentDone,
                     TorrentGetField::HashString,
+                    TorrentGetField::PeersConnected,
+                    TorrentGetField::RateDownload,
+                    TorrentGetField::Eta,
+                    TorrentGetField::SizeWhenDone,
                 ]),
-                Some(vec![types::Id::Id(id)]),
+                Some(ids.iter().map(|id| types::Id::Id(*id)).collect()),
             )
             .await
             .map_err(|e| anyhow!("failed to get torrent: {:?}", e))?;
-        let torrent = torrent.arguments.torrents.into_iter().next();
-        Ok(match torrent {
-            None => None,
-            Some(torrent) => Some(TorrentInfo {
-                id,
-                hash: torrent.hash_string.unwrap_or_default(),
-                status: if torrent.is_finished.unwrap_or(false) || torrent.percent_done == Some(1.0)
-                {
-                    TorrentStatus::Finished
-                } else {
-                    TorrentStatus::InProgress
-                },
-            }),
-        })
+        Ok(torrents
+            .arguments
+            .torrents
+            .into_iter()
+            .filter_map(|torrent| {
+                Some(TorrentInfo {
+                    id: torrent.id?,
+                    hash: torrent.hash_string.unwrap_or_default(),
+                    status: if torrent.is_finished.unwrap_or(false)
+                        || torrent.percent_done == Some(1.0)
+                    {
+                        TorrentStatus::Finished
+                    } else if torrent.status == Some(STATUS_DOWNLOAD) {
+                        TorrentStatus::Downloading
+                    } else {
+                        TorrentStatus::Idle
+                    },
+                    percent_done: torrent.percent_done.unwrap_or_default(),
+                    peers: torrent.peers_connected.unwrap_or_default().max(0) as u64,
+                    rate_download: torrent.rate_download.unwrap_or_default().max(0) as u64,
+                    // transmission reports unknown/unavailable as negative values
+                    eta_seconds: torrent.eta.filter(|x| *x >= 0).map(|x| x as u64),
+                    size: torrent.size_when_done.unwrap_or_default().max(0) as u64,
+                })
+            })
+            .collect())
     }
 
     async fn finished(&mut self) -> Result<Vec<FinishedTorrent>> {
@@ -1,4 +1,6 @@
 mod nyaa;
+use std::sync::Arc;
+
 pub use nyaa::{NyaaClient, NyaaConfig};
 
 use anyhow::Result;
@@ -11,7 +13,7 @@ pub trait Source: Send + Sync {
 }
 
 #[async_trait::async_trait]
-impl Source for Box<dyn Source + Send + Sync> {
+impl Source for Arc<dyn Source + Send + Sync> {
     async fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
         Source::search(&**self, query).await
     }
@@ -32,6 +32,7 @@ pub struct NyaaResult {
     seeders: u64,
     leechers: u64,
     downloads: u64,
+    info_hash: Option<String>,
     /// Anime - English-translated
     #[allow(dead_code)]
     category: String,
@@ -40,23 +41,24 @@ pub struct NyaaResult {
     trusted: bool,
 }
 
-impl Into<SearchResult> for NyaaResult {
-    fn into(self) -> SearchResult {
+impl From<NyaaResult> for SearchResult {
+    fn from(value: NyaaResult) -> Self {
         SearchResult {
-            title: self.title,
-            torrent_link: self.torrent_link,
-            view_link: self.view_link,
-            date: self.date,
-            seeders: self.seeders,
-            leechers: self.leechers,
-            downloads: self.downloads,
-            size: self.size,
+            title: value.title,
+            torrent_link: value.torrent_link,
+            view_link: value.view_link,
+            date: value.date,
+            seeders: value.seeders,
+            leechers: value.leechers,
+            downloads: value.downloads,
+            size: value.size,
+            info_hash: value.info_hash,
         }
     }
 }
 
 fn size_parse(input: &str) -> Option<u64> {
-    let input_prefix = input.splitn(2, ' ').next()?;
+    let input_prefix = input.split(' ').next()?;
     let input_prefix = input_prefix.parse::<f64>().ok()?;
     let out = if input.ends_with(" GiB") || input.ends_with(" GB") {
         Some(input_prefix * 1024.0 * 1024.0 * 1024.0)
@@ -106,7 +108,7 @@ impl NyaaClient {
                 view_link: item.guid.map(|x| x.value).unwrap_or_default(),
                 date: item
                     .pub_date
-                    .and_then(|x| DateTime::parse_from_str(&*x, "%a, %d %b %Y %H:%M:%S %z").ok())
+                    .and_then(|x| DateTime::parse_from_str(&x, "%a, %d %b %Y %H:%M:%S %z").ok())
                     .ok_or_else(|| anyhow!("no date"))?,
                 seeders: nyaa
                     .remove("seeders")
@@ -126,13 +128,17 @@ impl NyaaClient {
                     .and_then(|x| x.value)
                     .and_then(|x| x.parse().ok())
                     .unwrap_or_default(),
+                info_hash: nyaa
+                    .remove("infoHash")
+                    .and_then(|x| x.into_iter().next())
+                    .and_then(|x| x.value),
                 category: nyaa
                     .remove("category")
                     .and_then(|x| x.into_iter().next())
                     .and_then(|x| x.value)
                     .unwrap_or_default(),
                 size: size_parse(
-                    &*nyaa
+                    &nyaa
                         .remove("size")
                         .and_then(|x| x.into_iter().next())
                         .and_then(|x| x.value)